@@ -213,14 +213,6 @@ async fn load_obstacles(
     Ok(result)
 }
 
-#[derive(geng::asset::Load, Deserialize)]
-#[load(serde = "json")]
-pub struct ObstacleConfig {
-    pub hitbox_origin: vec2<f32>,
-    pub hitbox_radius: f32,
-    pub spawn_weight: f32,
-}
-
 pub struct ObstacleAssets {
     pub config: ObstacleConfig,
     pub texture: Texture,
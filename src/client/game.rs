@@ -9,6 +9,8 @@ pub struct Particle {
     i_opacity: f32,
 }
 
+const ANNOUNCEMENT_DURATION: f32 = 5.0;
+
 fn can_detonate(x: f32) -> bool {
     x >= -1.0 && x < 2.0
 }
@@ -21,12 +23,13 @@ pub struct Game {
     time: f32,
     volume: f32,
     explosion_time: Option<f32>,
+    announcement: Option<(String, f32)>,
     last_model_tick: u64,
     geng: Geng,
     assets: Rc<Assets>,
     player_id: Id,
     camera: geng::Camera2d,
-    model: simple_net::Remote<Model>,
+    model: simple_net::Remote<Protocol>,
     players: Collection<Player>,
     interpolated_players: Collection<Player>,
     player_skin_renderers: HashMap<Id, skin::Renderer>,
@@ -61,6 +64,9 @@ pub enum UiMessage {
 }
 
 impl Game {
+    pub fn announce(&mut self, text: &str) {
+        self.announcement = Some((text.to_lowercase(), self.time));
+    }
     fn ui_buttons(&self) -> Vec<ui::Button<UiMessage>> {
         vec![ui::Button::new(
             "menu",
@@ -94,7 +100,7 @@ impl Game {
         player_id: Id,
         name: Option<String>,
         config: Option<skin::Config>,
-        model: simple_net::Remote<Model>,
+        model: simple_net::Remote<Protocol>,
     ) -> Self {
         Self {
             minimap_full: false,
@@ -109,6 +115,7 @@ impl Game {
                 .unwrap_or(0.5)
                 .clamp(0.0, 1.0),
             show_player_names: true,
+            announcement: None,
             explosion_time: None,
             geng: geng.clone(),
             assets: assets.clone(),
@@ -321,6 +328,8 @@ impl Game {
     }
 
     fn update_interpolated(&mut self, delta_time: f32) {
+        // Catch up over about three server updates, 0.3 seconds at the default rate
+        let interpolation_time = 3.0 / self.model.rates().updates_per_second;
         self.interpolated_players
             .retain(|player| self.players.get(&player.id).is_some());
         for player in &self.players {
@@ -328,14 +337,13 @@ impl Game {
                 self.interpolated_players.insert(player.clone());
             }
             let i = self.interpolated_players.get_mut(&player.id).unwrap();
-            const EXPECTED_PING: f32 = 0.3;
             *i = Player {
                 state: match (i.state, player.state) {
                     (
                         PlayerState::Parachute { timer: from },
                         PlayerState::Parachute { timer: to },
                     ) => Some(PlayerState::Parachute {
-                        timer: from + (to - from) / EXPECTED_PING * delta_time,
+                        timer: from + (to - from) / interpolation_time * delta_time,
                     }),
                     (_, PlayerState::Parachute { timer: to }) => {
                         Some(PlayerState::Parachute { timer: to })
@@ -355,12 +363,15 @@ impl Game {
                 id: player.id,
                 emote: player.emote,
                 name: player.name.clone(),
-                position: i.position + (player.position - i.position) / EXPECTED_PING * delta_time,
+                position: i.position
+                    + (player.position - i.position) / interpolation_time * delta_time,
                 config: player.config.clone(),
                 radius: player.radius,
-                rotation: i.rotation + (player.rotation - i.rotation) / EXPECTED_PING * delta_time,
+                rotation: i.rotation
+                    + (player.rotation - i.rotation) / interpolation_time * delta_time,
                 input: player.input,
-                velocity: i.velocity + (player.velocity - i.velocity) / EXPECTED_PING * delta_time,
+                velocity: i.velocity
+                    + (player.velocity - i.velocity) / interpolation_time * delta_time,
                 seen_no_avalanche: player.seen_no_avalanche,
                 ride_volume: player.ride_volume,
             };
@@ -722,6 +733,7 @@ impl geng::State for Game {
                                             ski_velocity: player.velocity,
                                             ski_rotation: player.rotation,
                                             crash_position: player.position,
+                                            cause: CrashCause::Obstacle,
                                         };
                                         sounds.push((&self.assets.crash_sounds, player.position));
                                     }
@@ -737,6 +749,7 @@ impl geng::State for Game {
                                         ski_velocity: player.velocity,
                                         ski_rotation: player.rotation,
                                         crash_position: player.position,
+                                        cause: CrashCause::Border,
                                     };
                                     sounds.push((&self.assets.crash_sounds, player.position));
                                 }
@@ -760,6 +773,7 @@ impl geng::State for Game {
                                 ski_velocity: player.velocity,
                                 ski_rotation: player.rotation,
                                 crash_position: player.position,
+                                cause: CrashCause::Avalanche,
                             };
                             sounds.push((&self.assets.crash_sounds, player.position));
                         }
@@ -829,8 +843,20 @@ impl geng::State for Game {
                 self.model.send(Message::UpdatePlayer(player.clone()));
             }
 
-            for _event in self.model.update() {
-                // TODO handle
+            for event in self.model.update() {
+                match event {
+                    Event::Announcement(text) => self.announce(&text),
+                    Event::NameRefused { player_id, reason } => {
+                        if player_id == self.player_id {
+                            self.announce(&reason);
+                        }
+                    }
+                    // Answers to lobby requests
+                    Event::Leaderboard { .. } | Event::Profile { .. } | Event::Season { .. } => {}
+                }
+            }
+            if self.model.is_closed() {
+                self.transition = Some(geng::state::Transition::Pop);
             }
 
             for (sounds, pos) in sounds {
@@ -1214,7 +1240,10 @@ impl geng::State for Game {
                                 / model.config.player.parachute_time,
                         ),
                     0.5,
-                    &player.name,
+                    &match model.ratings.get(&player.name) {
+                        Some(rating) => format!("{} {}", player.name, rating),
+                        None => player.name.clone(),
+                    },
                     0.5,
                     Rgba::WHITE,
                 );
@@ -1350,6 +1379,19 @@ impl geng::State for Game {
                 Rgba::WHITE,
             );
         }
+        if let Some((text, time)) = &self.announcement {
+            if self.time - time < ANNOUNCEMENT_DURATION {
+                self.assets.font.draw(
+                    framebuffer,
+                    &self.camera,
+                    self.camera.center + vec2(0.0, 5.0),
+                    1.0,
+                    text,
+                    0.5,
+                    Rgba::WHITE,
+                );
+            }
+        }
         if let Some(target_player) = target_player {
             if let PlayerState::Ride { .. } | PlayerState::Crash { .. } | PlayerState::Walk =
                 &target_player.state
@@ -13,6 +13,16 @@ pub enum UiMessage {
     ChangeEquipment,
     SecretSkin,
     Leaderboard,
+    NextBoard,
+    PrevPage,
+    NextPage,
+    ToggleAroundMe,
+    MyProfile,
+    ShowProfile(usize),
+    Seasons,
+    PrevSeason,
+    NextSeason,
+    Tournament,
     Play,
     Customize,
     Spectate,
@@ -25,6 +35,9 @@ pub enum State {
     Leaderboard,
     Customizer,
     Keyboard,
+    Profile,
+    Seasons,
+    Tournament,
 }
 
 pub struct Lobby {
@@ -32,9 +45,16 @@ pub struct Lobby {
     framebuffer_size: vec2<usize>,
     assets: Rc<Assets>,
     player_id: Id,
-    model: simple_net::Remote<Model>,
+    model: simple_net::Remote<Protocol>,
     transition: Option<geng::state::Transition>,
     name: String,
+    name_error: Option<String>,
+    board: Board,
+    view: LeaderboardView,
+    leaderboard: Option<LeaderboardPage>,
+    profile: Option<Profile>,
+    profile_return: State,
+    season: Option<SeasonSummary>,
     camera: geng::Camera2d,
     mouse: vec2<f32>,
     config: skin::Config,
@@ -48,7 +68,7 @@ impl Lobby {
         geng: &Geng,
         assets: &Rc<Assets>,
         player_id: Id,
-        model: simple_net::Remote<Model>,
+        model: simple_net::Remote<Protocol>,
     ) -> Self {
         let random_config = skin::Config::random(&assets.player);
         let config = match preferences::load("player.json") {
@@ -105,10 +125,17 @@ impl Lobby {
             player_id,
             model,
             transition: None,
-            name: match preferences::load("player_name.txt") {
-                Some(name) => name,
+            name: match preferences::load::<String>("player_name.txt") {
+                Some(name) => normalize_name(&name),
                 None => String::new(),
             },
+            name_error: None,
+            board: Board::AllTime,
+            view: LeaderboardView::Page(0),
+            leaderboard: None,
+            profile: None,
+            profile_return: State::Main,
+            season: None,
             mouse: vec2::ZERO,
             skin_renderer: skin::Renderer::new(geng, &config, assets),
             config,
@@ -116,6 +143,38 @@ impl Lobby {
             ui_controller: ui::Controller::new(geng, assets),
         }
     }
+    fn show_profile(&mut self, name: String) {
+        self.model.send(Message::RequestProfile(name));
+        self.profile = None;
+        self.profile_return = self.state;
+        self.state = State::Profile;
+    }
+    /// Leaderboard rows with their height, the player's own entry goes last if it's off the page
+    fn leaderboard_rows(&self) -> Vec<(f32, &LeaderboardEntry)> {
+        let page = match &self.leaderboard {
+            Some(page) => page,
+            None => return Vec::new(),
+        };
+        let mut rows: Vec<(f32, &LeaderboardEntry)> = page
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (1.0 - index as f32 * 0.1, entry))
+            .collect();
+        if let Some(own) = &page.own {
+            if !page.entries.contains(own) {
+                rows.push((0.9 - page.entries.len() as f32 * 0.1, own));
+            }
+        }
+        rows
+    }
+    fn request_leaderboard(&self) {
+        self.model
+            .send(Message::RequestLeaderboard(LeaderboardRequest {
+                board: self.board,
+                view: self.view,
+            }));
+    }
     fn handle_ui(&mut self, message: UiMessage) {
         fn change_skin_item<T>(item: &mut Option<String>, options: &HashMap<String, T>) {
             let options: Vec<&str> = options.keys().map(|s| s.as_str()).collect();
@@ -126,14 +185,32 @@ impl Lobby {
         }
         match message {
             UiMessage::Input(c) => {
-                if self.name.len() < 15 {
+                if self.name.len() < MAX_NAME_LEN && NAME_CHARS.contains(c) {
                     self.name.push(c);
                 }
+                self.name_error = None;
             }
             UiMessage::Delete => {
                 self.name.pop();
+                self.name_error = None;
+            }
+            UiMessage::Back => {
+                self.state = match self.state {
+                    State::Profile => self.profile_return,
+                    State::Seasons => State::Leaderboard,
+                    _ => State::Main,
+                }
+            }
+            UiMessage::MyProfile => self.show_profile(self.name.clone()),
+            UiMessage::ShowProfile(index) => {
+                let name = self
+                    .leaderboard_rows()
+                    .get(index)
+                    .map(|(_, entry)| entry.name.clone());
+                if let Some(name) = name {
+                    self.show_profile(name);
+                }
             }
-            UiMessage::Back => self.state = State::Main,
             UiMessage::RandomSkin => self.config = skin::Config::random(&self.assets.player),
             UiMessage::ChangeHat => change_skin_item(&mut self.config.hat, &self.assets.player.hat),
             UiMessage::ChangeFace => {
@@ -164,8 +241,63 @@ impl Lobby {
                     };
                 }
             }
-            UiMessage::Leaderboard => self.state = State::Leaderboard,
+            UiMessage::Leaderboard => {
+                self.state = State::Leaderboard;
+                self.request_leaderboard();
+            }
+            UiMessage::NextBoard => {
+                let index = Board::ALL.iter().position(|&board| board == self.board);
+                self.board = Board::ALL[index.map_or(0, |index| (index + 1) % Board::ALL.len())];
+                if let LeaderboardView::Page(_) = self.view {
+                    self.view = LeaderboardView::Page(0);
+                }
+                self.request_leaderboard();
+            }
+            UiMessage::PrevPage => {
+                if let LeaderboardView::Page(page) = self.view {
+                    self.view = LeaderboardView::Page(page.saturating_sub(1));
+                    self.request_leaderboard();
+                }
+            }
+            UiMessage::NextPage => {
+                if let LeaderboardView::Page(page) = self.view {
+                    self.view = LeaderboardView::Page(page + 1);
+                    self.request_leaderboard();
+                }
+            }
+            UiMessage::Tournament => self.state = State::Tournament,
+            UiMessage::Seasons => {
+                self.state = State::Seasons;
+                self.model.send(Message::RequestSeason(None));
+            }
+            UiMessage::PrevSeason => {
+                if let Some(season) = &self.season {
+                    if season.number > 1 {
+                        self.model
+                            .send(Message::RequestSeason(Some(season.number - 1)));
+                    }
+                }
+            }
+            UiMessage::NextSeason => {
+                if let Some(season) = &self.season {
+                    if season.number < season.finished {
+                        self.model
+                            .send(Message::RequestSeason(Some(season.number + 1)));
+                    }
+                }
+            }
+            UiMessage::ToggleAroundMe => {
+                self.view = match self.view {
+                    LeaderboardView::AroundMe => LeaderboardView::Page(0),
+                    LeaderboardView::Page(_) => LeaderboardView::AroundMe,
+                };
+                self.request_leaderboard();
+            }
             UiMessage::Play => {
+                if let Err(reason) = validate_name(&self.name) {
+                    self.name_error = Some(reason);
+                    return;
+                }
                 self.transition = Some(geng::state::Transition::Switch(Box::new(Game::new(
                     &self.geng,
                     &self.assets,
@@ -317,6 +449,67 @@ impl Lobby {
                 result
             }
             State::Leaderboard => {
+                let mut result = vec![
+                    ui::Button::new(
+                        self.board.title(),
+                        vec2(0.0, -0.18),
+                        0.07,
+                        0.5,
+                        UiMessage::NextBoard,
+                    ),
+                    ui::Button::new("back", vec2(0.0, -0.35), 0.15, 0.5, UiMessage::Back),
+                    ui::Button::new(
+                        match self.view {
+                            LeaderboardView::AroundMe => "top",
+                            LeaderboardView::Page(_) => "around me",
+                        },
+                        vec2(0.0, -0.47),
+                        0.07,
+                        0.5,
+                        UiMessage::ToggleAroundMe,
+                    ),
+                    ui::Button::new(
+                        "past seasons",
+                        vec2(0.0, -0.59),
+                        0.07,
+                        0.5,
+                        UiMessage::Seasons,
+                    ),
+                ];
+                for (index, (y, entry)) in self.leaderboard_rows().into_iter().enumerate() {
+                    result.push(ui::Button::new(
+                        &entry.name,
+                        vec2(-0.9, y),
+                        0.1,
+                        0.0,
+                        UiMessage::ShowProfile(index),
+                    ));
+                }
+                if let (LeaderboardView::Page(page), Some(leaderboard)) =
+                    (self.view, &self.leaderboard)
+                {
+                    if page > 0 {
+                        result.push(ui::Button::new(
+                            "prev",
+                            vec2(-1.0, -0.18),
+                            0.07,
+                            0.0,
+                            UiMessage::PrevPage,
+                        ));
+                    }
+                    if (page + 1) * LEADERBOARD_PAGE_SIZE < leaderboard.total {
+                        result.push(ui::Button::new(
+                            "next",
+                            vec2(1.0, -0.18),
+                            0.07,
+                            1.0,
+                            UiMessage::NextPage,
+                        ));
+                    }
+                }
+                result
+            }
+            State::Profile => {
                 vec![ui::Button::new(
                     "back",
                     vec2(0.0, -0.35),
@@ -325,12 +518,52 @@ impl Lobby {
                     UiMessage::Back,
                 )]
             }
+            State::Tournament => {
+                vec![ui::Button::new(
+                    "back",
+                    vec2(0.0, -0.35),
+                    0.15,
+                    0.5,
+                    UiMessage::Back,
+                )]
+            }
+            State::Seasons => {
+                let mut result = vec![ui::Button::new(
+                    "back",
+                    vec2(0.0, -0.35),
+                    0.15,
+                    0.5,
+                    UiMessage::Back,
+                )];
+                if let Some(season) = &self.season {
+                    if season.number > 1 {
+                        result.push(ui::Button::new(
+                            "older",
+                            vec2(-1.0, -0.18),
+                            0.07,
+                            0.0,
+                            UiMessage::PrevSeason,
+                        ));
+                    }
+                    if season.number < season.finished {
+                        result.push(ui::Button::new(
+                            "newer",
+                            vec2(1.0, -0.18),
+                            0.07,
+                            1.0,
+                            UiMessage::NextSeason,
+                        ));
+                    }
+                }
+                result
+            }
             State::Main => {
                 let size = 0.1;
                 let mut result = vec![
                     ui::Button::new("customize", vec2(0.0, 0.8), size, 0.0, UiMessage::Customize),
                     ui::Button::new("play", vec2(0.0, 0.4), size * 2.0, 0.0, UiMessage::Play),
                     ui::Button::new("spectate", vec2(0.0, 0.0), size, 0.0, UiMessage::Spectate),
+                    ui::Button::new("profile", vec2(0.0, -0.15), size, 0.0, UiMessage::MyProfile),
                     ui::Button::new(
                         "join discord",
                         vec2(0.0, -0.3),
@@ -346,6 +579,15 @@ impl Lobby {
                         UiMessage::Leaderboard,
                     ),
                 ];
+                if self.model.get().tournament.is_some() {
+                    result.push(ui::Button::new(
+                        "tournament",
+                        vec2(0.0, -0.45),
+                        size,
+                        0.0,
+                        UiMessage::Tournament,
+                    ));
+                }
                 if self.assets.player.secret.contains_key(&self.name) {
                     result.push(ui::Button::new(
                         "secret",
@@ -380,88 +622,217 @@ impl geng::State for Lobby {
                     0.5,
                     Rgba::GRAY,
                 );
-                {
-                    let model = &self.model;
-                    let mut rows = Vec::new();
-                    let model = model.get();
-                    for (name, score) in &model.highscores {
-                        rows.push((name.clone(), *score));
-                    }
-                    rows.sort_by_key(|(name, score)| (-*score, name.clone()));
-                    while rows.len() > 10 {
-                        rows.pop();
+                let own = self.leaderboard.as_ref().and_then(|page| page.own.as_ref());
+                let highlight = Rgba::opaque(0.5, 0.5, 1.0);
+                for (y, entry) in self.leaderboard_rows() {
+                    let color = if own == Some(entry) {
+                        highlight
+                    } else {
+                        Rgba::WHITE
+                    };
+                    self.assets.font.draw(
+                        framebuffer,
+                        &self.camera,
+                        vec2(-1.0, y),
+                        0.1,
+                        &entry.rank.to_string(),
+                        1.0,
+                        color,
+                    );
+                    self.assets.font.draw(
+                        framebuffer,
+                        &self.camera,
+                        vec2(1.0, y),
+                        0.1,
+                        &entry.score.to_string(),
+                        1.0,
+                        color,
+                    );
+                    if let (Some(rating), false) = (entry.rating, self.board == Board::Rating) {
+                        self.assets.font.draw(
+                            framebuffer,
+                            &self.camera,
+                            vec2(0.5, y),
+                            0.07,
+                            &rating.to_string(),
+                            1.0,
+                            Rgba::GRAY,
+                        );
                     }
-                    if !rows.iter().any(|(name, _score)| name == &self.name) {
-                        if let Some(&score) = model.highscores.get(&self.name) {
-                            rows.push((self.name.clone(), score));
+                }
+            }
+            State::Tournament => {
+                let model = self.model.get();
+                match &model.tournament {
+                    Some(tournament) => {
+                        self.assets.font.draw(
+                            framebuffer,
+                            &self.camera,
+                            vec2(0.0, 1.2),
+                            0.15,
+                            &tournament.name,
+                            0.5,
+                            Rgba::GRAY,
+                        );
+                        self.assets.font.draw(
+                            framebuffer,
+                            &self.camera,
+                            vec2(0.0, 1.1),
+                            0.07,
+                            &format!(
+                                "heat {} of {} scoring by {}",
+                                tournament.results.len().min(tournament.heats),
+                                tournament.heats,
+                                tournament.scoring.name(),
+                            ),
+                            0.5,
+                            Rgba::GRAY,
+                        );
+                        for (index, entry) in tournament.standings().iter().enumerate() {
+                            let y = 0.95 - index as f32 * 0.1;
+                            let color = if entry.name == self.name {
+                                Rgba::opaque(0.5, 0.5, 1.0)
+                            } else {
+                                Rgba::WHITE
+                            };
+                            for (x, text, align) in [
+                                (-1.0, entry.rank.to_string(), 1.0),
+                                (-0.9, entry.name.clone(), 0.0),
+                                (1.0, entry.score.to_string(), 1.0),
+                            ] {
+                                self.assets.font.draw(
+                                    framebuffer,
+                                    &self.camera,
+                                    vec2(x, y),
+                                    0.1,
+                                    &text,
+                                    align,
+                                    color,
+                                );
+                            }
                         }
                     }
-                    let mut y = 1.0;
-                    let highlight = Rgba::opaque(0.5, 0.5, 1.0);
-                    for (index, (name, _score)) in rows.iter().enumerate() {
-                        if index == 10 {
-                            y -= 0.1;
-                        }
-                        let color = if *name == self.name {
-                            highlight
-                        } else {
-                            Rgba::WHITE
-                        };
-                        if index < 10 {
+                    None => {
+                        self.assets.font.draw(
+                            framebuffer,
+                            &self.camera,
+                            vec2(0.0, 1.2),
+                            0.15,
+                            "no tournament",
+                            0.5,
+                            Rgba::GRAY,
+                        );
+                    }
+                }
+            }
+            State::Seasons => {
+                self.assets.font.draw(
+                    framebuffer,
+                    &self.camera,
+                    vec2(0.0, 1.2),
+                    0.2,
+                    &match &self.season {
+                        Some(season) => format!("season {}", season.number),
+                        None => "no past seasons".to_owned(),
+                    },
+                    0.5,
+                    Rgba::GRAY,
+                );
+                if let Some(season) = &self.season {
+                    for (index, entry) in season.top.iter().enumerate() {
+                        let y = 1.0 - index as f32 * 0.1;
+                        for (x, text, align) in [
+                            (-1.0, entry.rank.to_string(), 1.0),
+                            (-0.9, entry.name.clone(), 0.0),
+                            (1.0, entry.score.to_string(), 1.0),
+                        ] {
                             self.assets.font.draw(
                                 framebuffer,
                                 &self.camera,
-                                vec2(-1.0, y),
+                                vec2(x, y),
                                 0.1,
-                                &(index + 1).to_string(),
-                                1.0,
-                                color,
+                                &text,
+                                align,
+                                Rgba::WHITE,
                             );
                         }
-                        y -= 0.1;
                     }
-                    let mut y = 1.0;
-                    for (index, (name, _score)) in rows.iter().enumerate() {
-                        if index == 10 {
-                            y -= 0.1;
-                        }
-                        let color = if *name == self.name {
-                            highlight
-                        } else {
-                            Rgba::WHITE
-                        };
+                }
+            }
+            State::Profile => {
+                if let Some(profile) = &self.profile {
+                    self.assets.font.draw(
+                        framebuffer,
+                        &self.camera,
+                        vec2(0.0, 1.2),
+                        0.15,
+                        &profile.name,
+                        0.5,
+                        Rgba::GRAY,
+                    );
+                    let stats = &profile.stats;
+                    let optional =
+                        |value: Option<String>| value.unwrap_or_else(|| "none".to_owned());
+                    let mut rows = vec![
+                        (
+                            "best score",
+                            optional(
+                                profile
+                                    .highscore
+                                    .as_ref()
+                                    .map(|entry| entry.score.to_string()),
+                            ),
+                        ),
+                        (
+                            "rank",
+                            optional(
+                                profile
+                                    .highscore
+                                    .as_ref()
+                                    .map(|entry| entry.rank.to_string()),
+                            ),
+                        ),
+                        (
+                            "rating",
+                            optional(stats.rating.map(|rating| rating.round().to_string())),
+                        ),
+                        ("races started", stats.races_started.to_string()),
+                        ("races finished", stats.races_finished.to_string()),
+                        (
+                            "best place",
+                            optional(stats.best_place.map(|place| place.to_string())),
+                        ),
+                        (
+                            "average score",
+                            optional(stats.average_score().map(|score| score.to_string())),
+                        ),
+                        ("distance", format!("{}m", stats.total_distance as i64)),
+                    ];
+                    rows.extend(
+                        CrashCause::ALL
+                            .iter()
+                            .map(|&cause| (cause.label(), stats.crashes(cause).to_string())),
+                    );
+                    for (index, (label, value)) in rows.iter().enumerate() {
+                        let y = 1.0 - index as f32 * 0.1;
                         self.assets.font.draw(
                             framebuffer,
                             &self.camera,
-                            vec2(-0.9, y),
-                            0.1,
-                            name,
+                            vec2(-1.0, y),
+                            0.08,
+                            label,
                             0.0,
-                            color,
+                            Rgba::WHITE,
                         );
-                        y -= 0.1;
-                    }
-                    let mut y = 1.0;
-                    for (index, (name, score)) in rows.iter().enumerate() {
-                        if index == 10 {
-                            y -= 0.1;
-                        }
-
-                        let color = if *name == self.name {
-                            highlight
-                        } else {
-                            Rgba::WHITE
-                        };
                         self.assets.font.draw(
                             framebuffer,
                             &self.camera,
                             vec2(1.0, y),
-                            0.1,
-                            &score.to_string(),
+                            0.08,
+                            value,
                             1.0,
-                            color,
+                            Rgba::WHITE,
                         );
-                        y -= 0.1;
                     }
                 }
             }
@@ -510,6 +881,28 @@ impl geng::State for Lobby {
                         c.unwrap_or(Rgba::WHITE),
                     );
                 }
+                if let Some(error) = &self.name_error {
+                    self.assets.font.draw(
+                        framebuffer,
+                        &self.camera,
+                        vec2(0.5, 1.0),
+                        0.05,
+                        error,
+                        0.5,
+                        Rgba::RED,
+                    );
+                }
+                if let Some(preset) = &self.model.get().preset {
+                    self.assets.font.draw(
+                        framebuffer,
+                        &self.camera,
+                        vec2(0.0, 1.35),
+                        0.07,
+                        &format!("mode {}", preset.to_lowercase()),
+                        0.5,
+                        Rgba::GRAY,
+                    );
+                }
             }
         }
 
@@ -518,7 +911,17 @@ impl geng::State for Lobby {
     }
 
     fn update(&mut self, _delta_time: f64) {
-        self.model.update();
+        for event in self.model.update() {
+            match event {
+                Event::Leaderboard { page, .. } => self.leaderboard = Some(page),
+                Event::Profile { profile, .. } => self.profile = Some(profile),
+                Event::Season { season, .. } => self.season = season,
+                _ => {}
+            }
+        }
+        if self.model.is_closed() {
+            self.transition = Some(geng::state::Transition::Pop);
+        }
     }
 
     fn handle_event(&mut self, event: geng::Event) {
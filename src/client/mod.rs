@@ -11,11 +11,21 @@ pub fn run(
     geng: &Geng,
     assets: &Rc<Assets>,
     player_id: Id,
-    opt: &Opt,
-    model: simple_net::Remote<Model>,
+    requested_role: Role,
+    handshake: Handshake,
+    model: simple_net::Remote<Protocol>,
 ) -> Box<dyn geng::State> {
-    if opt.spectator {
-        Box::new(Game::new(&geng, assets, player_id, None, None, model))
+    if let Some(token) = &handshake.token {
+        if preferences::load::<String>("player_token.txt").as_ref() != Some(token) {
+            preferences::save("player_token.txt", token);
+        }
+    }
+    if handshake.role == Role::Spectator {
+        let mut game = Game::new(&geng, assets, player_id, None, None, model);
+        if requested_role == Role::Player {
+            game.announce("server is full you are spectating");
+        }
+        Box::new(game)
     } else {
         Box::new(Lobby::new(&geng, assets, player_id, model))
     }
@@ -1,7 +1,5 @@
 use super::*;
 
-const CHARS: &str = "abcdefghijklmnopqrstuvwxyz0123456789";
-
 pub struct Font {
     draw2d: geng::draw2d::Helper,
     indices: HashMap<char, usize>,
@@ -102,7 +100,7 @@ impl geng::asset::Load for Font {
         async move {
             let mut textures = Vec::new();
             let mut indices = HashMap::new();
-            for c in CHARS.chars() {
+            for c in NAME_CHARS.chars() {
                 indices.insert(c, textures.len());
                 textures.push(<ugli::Texture as geng::asset::Load>::load(
                     &manager,
@@ -1,52 +1,141 @@
+#[cfg(not(feature = "client"))]
+use batbox::prelude::*;
+#[cfg(feature = "client")]
 use geng::prelude::*;
 
+#[cfg(feature = "client")]
 mod assets;
+#[cfg(feature = "client")]
 mod client;
-mod discord;
+#[cfg(feature = "client")]
 mod font;
 mod model;
+#[cfg(feature = "server")]
 mod server;
 mod simple_net;
 mod skin;
+#[cfg(feature = "client")]
 mod ui;
 
+#[cfg(feature = "client")]
 use assets::*;
+#[cfg(feature = "client")]
 use font::*;
 use model::*;
+#[cfg(feature = "server")]
 use server::Model;
 
+#[cfg(feature = "client")]
 const DISCORD_LINK: &str = "https://discord.gg/DZaEMPpANY";
 
 fn assets_path() -> std::path::PathBuf {
     run_dir().join("assets")
 }
 
+#[cfg(feature = "server")]
+#[derive(clap::Subcommand, Clone)]
+pub enum Command {
+    /// Show recently finished races
+    Races {
+        #[clap(long, default_value = "10")]
+        count: usize,
+    },
+    /// Write standings as an html page and csv files
+    Export {
+        #[clap(long, default_value = "export")]
+        out: std::path::PathBuf,
+    },
+}
+
+/// Rates turn into sleep durations, so they have to be positive and finite
+#[cfg(feature = "server")]
+fn parse_rate(s: &str) -> Result<f32, String> {
+    let rate: f32 = s.parse().map_err(|e| format!("Bad rate: {}", e))?;
+    if !rate.is_finite() || rate <= 0.0 {
+        return Err(format!("Rate must be a positive number, got {}", s));
+    }
+    Ok(rate)
+}
+
 #[derive(clap::Parser, Clone)]
 pub struct Opt {
+    #[cfg(feature = "server")]
+    #[clap(subcommand)]
+    command: Option<Command>,
     #[clap(long)]
     server: Option<String>,
     #[clap(long)]
     connect: Option<String>,
+    #[cfg(feature = "client")]
     #[clap(long)]
     spectator: bool,
+    #[cfg(feature = "client")]
     #[clap(long)]
     auto_sound: bool,
+    #[cfg(feature = "server")]
+    #[clap(long)]
+    compression_threshold: Option<usize>,
+    #[cfg(feature = "server")]
+    #[clap(long, default_value = "10", value_parser = parse_rate)]
+    tick_rate: f32,
+    #[cfg(feature = "server")]
+    #[clap(long, default_value = "10", value_parser = parse_rate)]
+    update_rate: f32,
+    #[cfg(feature = "server")]
+    #[clap(long)]
+    restart_eta: Option<f32>,
+    #[cfg(feature = "server")]
+    #[clap(long)]
+    status: Option<String>,
+    #[cfg(feature = "server")]
+    #[clap(long)]
+    max_players: Option<usize>,
+    #[cfg(feature = "server")]
+    #[clap(long)]
+    max_connections: Option<usize>,
+    /// Connections allowed from a single address at once
+    #[cfg(feature = "server")]
+    #[clap(long)]
+    max_connections_per_address: Option<usize>,
+    #[cfg(feature = "server")]
+    #[clap(long)]
+    check_config: bool,
+    #[cfg(feature = "server")]
+    #[clap(long)]
+    preset: Option<String>,
+    /// UTC time of day when the daily and weekly leaderboards start over
+    #[cfg(feature = "server")]
+    #[clap(long, default_value = "00:00", value_parser = server::parse_time_of_day)]
+    rollover_time: u64,
+    /// Day of the week when the weekly leaderboard starts over
+    #[cfg(feature = "server")]
+    #[clap(long, default_value = "monday", value_parser = server::parse_weekday)]
+    rollover_day: u64,
+    /// YYYY-MM-DD dates (UTC) when the current season gets archived and the boards start over
+    #[cfg(feature = "server")]
+    #[clap(long, value_parser = server::parse_date)]
+    season_end: Vec<u64>,
+    #[cfg(feature = "client")]
     #[clap(flatten)]
     geng: geng::CliArgs,
 }
 
+#[cfg(feature = "client")]
 struct LoadingScreen {
     geng: Geng,
 }
 
+#[cfg(feature = "client")]
 impl LoadingScreen {
     fn new(geng: &Geng) -> Self {
         Self { geng: geng.clone() }
     }
 }
 
+#[cfg(feature = "client")]
 impl geng::ProgressScreen for LoadingScreen {}
 
+#[cfg(feature = "client")]
 impl geng::State for LoadingScreen {
     fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
         let framebuffer_size = framebuffer.size();
@@ -62,10 +151,151 @@ impl geng::State for LoadingScreen {
     }
 }
 
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+fn new_server(opt: &Opt, addr: &str) -> simple_net::Server<Model> {
+    let rollover = server::Rollover {
+        time: opt.rollover_time,
+        weekday: opt.rollover_day,
+    };
+    let model = match Model::new(
+        opt.max_players,
+        opt.preset.clone(),
+        rollover,
+        opt.season_end.clone(),
+    ) {
+        Ok(model) => model,
+        Err(e) => {
+            log::error!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+    let config = simple_net::ServerConfig {
+        compression_threshold: opt.compression_threshold,
+        max_connections: opt.max_connections,
+        max_connections_per_address: opt.max_connections_per_address,
+        rates: simple_net::Rates {
+            ticks_per_second: opt.tick_rate,
+            updates_per_second: opt.update_rate,
+        },
+    };
+    simple_net::Server::new(addr, config, model)
+}
+
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+fn run_server(opt: &Opt) {
+    let server = new_server(opt, opt.server.as_deref().unwrap());
+    let server_handle = server.handle();
+    let restart_eta = opt.restart_eta;
+    server::run_console(server_handle.clone());
+    if let Some(addr) = &opt.status {
+        server::run_status_server(addr, server_handle.clone());
+    }
+    ctrlc::set_handler(move || server_handle.shutdown(None, restart_eta))
+        .expect("Failed to set shutdown handler");
+    server.run();
+}
+
+#[cfg(feature = "client")]
+fn run_client(opt: Opt) {
+    #[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+    let server = opt.server.as_deref().map(|addr| {
+        let server = new_server(&opt, addr);
+        let server_handle = server.handle();
+        let server_thread = std::thread::spawn(move || {
+            server.run();
+        });
+        (server_handle, server_thread)
+    });
+    let role = if opt.spectator {
+        Role::Spectator
+    } else {
+        Role::Player
+    };
+    let game_constructor = move |geng: &Geng, player_id, handshake, model| {
+        geng::LoadingScreen::new(&geng, LoadingScreen::new(geng), {
+            let geng = geng.clone();
+            async move {
+                let mut assets: Assets = geng.asset_manager().load(assets_path()).await.unwrap();
+                assets.process(&geng).await;
+                client::run(&geng, &Rc::new(assets), player_id, role, handshake, model)
+            }
+        })
+    };
+
+    Geng::run_with(
+        &{
+            let mut options = geng::ContextOptions {
+                window: {
+                    let mut options = geng::window::Options::new("Extremely Extreme Sports");
+                    options.antialias = false;
+                    options
+                },
+                ..default()
+            };
+            options.with_cli(&opt.geng);
+            options
+        },
+        |geng| async move {
+            let handshake = Handshake {
+                name: normalize_name(
+                    &preferences::load::<String>("player_name.txt").unwrap_or_default(),
+                ),
+                skin: preferences::load("player.json"),
+                role,
+                room: None,
+                token: preferences::load("player_token.txt"),
+            };
+            let state = simple_net::ConnectingState::new(
+                &geng,
+                opt.connect.as_deref().unwrap(),
+                handshake,
+                {
+                    let geng = geng.clone();
+                    move |player_id, handshake, model| {
+                        game_constructor(&geng, player_id, handshake, model)
+                    }
+                },
+            );
+            geng.run_state(state).await
+        },
+    );
+
+    #[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+    if let Some((server_handle, server_thread)) = server {
+        server_handle.shutdown(None, None);
+        server_thread.join().unwrap();
+    }
+}
+
 fn main() {
     logger::init();
+    #[cfg(feature = "client")]
     geng::setup_panic_handler();
     let mut opt: Opt = cli::parse();
+    #[cfg(feature = "server")]
+    if let Some(command) = &opt.command {
+        let result = match command {
+            Command::Races { count } => server::print_recent_races(*count),
+            Command::Export { out } => server::export(out),
+        };
+        if let Err(e) = result {
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    #[cfg(feature = "server")]
+    if opt.check_config {
+        let errors = Model::check_config();
+        for e in &errors {
+            eprintln!("{:#}", e);
+        }
+        if !errors.is_empty() {
+            std::process::exit(1);
+        }
+        println!("Config is valid");
+        return;
+    }
     if opt.connect.is_none() && opt.server.is_none() {
         #[allow(clippy::option_env_unwrap)]
         if cfg!(target_arch = "wasm32") {
@@ -79,64 +309,13 @@ fn main() {
             opt.connect = Some("ws://127.0.0.1:1155".to_owned());
         }
     }
-    let model_constructor = Model::new;
-    let game_constructor = {
-        let opt = opt.clone();
-        move |geng: &Geng, player_id, model| {
-            geng::LoadingScreen::new(&geng, LoadingScreen::new(geng), {
-                let geng = geng.clone();
-                async move {
-                    let mut assets: Assets =
-                        geng.asset_manager().load(assets_path()).await.unwrap();
-                    assets.process(&geng).await;
-                    client::run(&geng, &Rc::new(assets), player_id, &opt, model)
-                }
-            })
-        }
-    };
-    if opt.server.is_some() && opt.connect.is_none() {
-        #[cfg(not(target_arch = "wasm32"))]
-        simple_net::Server::new(opt.server.as_deref().unwrap(), model_constructor()).run();
-    } else {
-        #[cfg(not(target_arch = "wasm32"))]
-        let server = if let Some(addr) = &opt.server {
-            let server = simple_net::Server::new(addr, model_constructor());
-            let server_handle = server.handle();
-            let server_thread = std::thread::spawn(move || {
-                server.run();
-            });
-            Some((server_handle, server_thread))
-        } else {
-            None
-        };
-
-        Geng::run_with(
-            &{
-                let mut options = geng::ContextOptions {
-                    window: {
-                        let mut options = geng::window::Options::new("Extremely Extreme Sports");
-                        options.antialias = false;
-                        options
-                    },
-                    ..default()
-                };
-                options.with_cli(&opt.geng);
-                options
-            },
-            |geng| async move {
-                let state =
-                    simple_net::ConnectingState::new(&geng, opt.connect.as_deref().unwrap(), {
-                        let geng = geng.clone();
-                        move |player_id, model| game_constructor(&geng, player_id, model)
-                    });
-                geng.run_state(state).await
-            },
-        );
-
-        #[cfg(not(target_arch = "wasm32"))]
-        if let Some((server_handle, server_thread)) = server {
-            server_handle.shutdown();
-            server_thread.join().unwrap();
-        }
+    #[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+    if opt.server.is_some() && (opt.connect.is_none() || !cfg!(feature = "client")) {
+        run_server(&opt);
+        return;
     }
+    #[cfg(feature = "client")]
+    run_client(opt);
+    #[cfg(not(feature = "client"))]
+    log::error!("This build has no client, run it with --server <address>");
 }
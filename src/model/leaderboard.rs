@@ -0,0 +1,178 @@
+use super::*;
+
+pub const LEADERBOARD_PAGE_SIZE: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Board {
+    Daily,
+    Weekly,
+    AllTime,
+    /// Players by rating instead of score
+    Rating,
+}
+
+#[cfg(feature = "client")]
+impl Board {
+    pub const ALL: [Self; 4] = [Self::Daily, Self::Weekly, Self::AllTime, Self::Rating];
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Daily => "today",
+            Self::Weekly => "this week",
+            Self::AllTime => "all time",
+            Self::Rating => "rating",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum LeaderboardView {
+    Page(usize),
+    /// The entries right above and below the requesting player
+    AroundMe,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub struct LeaderboardRequest {
+    pub board: Board,
+    pub view: LeaderboardView,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub name: String,
+    pub score: i32,
+    #[serde(default)]
+    pub rating: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LeaderboardPage {
+    pub request: LeaderboardRequest,
+    pub entries: Vec<LeaderboardEntry>,
+    pub total: usize,
+    /// The requesting player's own entry, wherever it is on the board
+    pub own: Option<LeaderboardEntry>,
+}
+
+/// Top of a finished season's all-time board
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SeasonSummary {
+    pub number: usize,
+    pub start: u64,
+    pub end: u64,
+    pub top: Vec<LeaderboardEntry>,
+    /// How many seasons have finished so far
+    pub finished: usize,
+}
+
+/// Sort scores best first, players with equal scores share a rank
+pub fn rank_scores<'a>(
+    scores: impl IntoIterator<Item = (&'a String, &'a i32)>,
+) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = scores
+        .into_iter()
+        .map(|(name, &score)| LeaderboardEntry {
+            rank: 0,
+            name: name.clone(),
+            score,
+            rating: None,
+        })
+        .collect();
+    entries.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    for index in 0..entries.len() {
+        entries[index].rank = if index > 0 && entries[index - 1].score == entries[index].score {
+            entries[index - 1].rank
+        } else {
+            index + 1
+        };
+    }
+    entries
+}
+
+impl LeaderboardPage {
+    pub fn new(request: LeaderboardRequest, ranked: &[LeaderboardEntry], name: &str) -> Self {
+        let own_index = ranked.iter().position(|entry| entry.name == name);
+        let range = match request.view {
+            LeaderboardView::Page(page) => {
+                let start = page * LEADERBOARD_PAGE_SIZE;
+                start.min(ranked.len())..(start + LEADERBOARD_PAGE_SIZE).min(ranked.len())
+            }
+            LeaderboardView::AroundMe => {
+                let center = own_index.unwrap_or(0);
+                let start = center
+                    .saturating_sub(LEADERBOARD_PAGE_SIZE / 2)
+                    .min(ranked.len().saturating_sub(LEADERBOARD_PAGE_SIZE));
+                start..(start + LEADERBOARD_PAGE_SIZE).min(ranked.len())
+            }
+        };
+        Self {
+            request,
+            entries: ranked[range].to_vec(),
+            total: ranked.len(),
+            own: own_index.map(|index| ranked[index].clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked(count: usize) -> Vec<LeaderboardEntry> {
+        let scores: HashMap<String, i32> = (0..count)
+            .map(|index| (format!("p{:02}", index), 1000 - index as i32))
+            .collect();
+        rank_scores(&scores)
+    }
+
+    fn request(view: LeaderboardView) -> LeaderboardRequest {
+        LeaderboardRequest {
+            board: Board::AllTime,
+            view,
+        }
+    }
+
+    #[test]
+    fn equal_scores_share_a_rank() {
+        let scores: HashMap<String, i32> = [("a", 5), ("b", 7), ("c", 5), ("d", 3), ("e", 7)]
+            .into_iter()
+            .map(|(name, score)| (name.to_owned(), score))
+            .collect();
+        let entries = rank_scores(&scores);
+        let ranks: Vec<(usize, &str)> = entries
+            .iter()
+            .map(|entry| (entry.rank, entry.name.as_str()))
+            .collect();
+        assert_eq!(ranks, [(1, "b"), (1, "e"), (3, "a"), (3, "c"), (5, "d")]);
+    }
+
+    #[test]
+    fn last_page_is_partial() {
+        let ranked = ranked(25);
+        let page = LeaderboardPage::new(request(LeaderboardView::Page(2)), &ranked, "");
+        assert_eq!(page.total, 25);
+        assert_eq!(page.entries.len(), 5);
+        assert_eq!(page.entries[0].rank, 21);
+        assert_eq!(page.own, None);
+        let page = LeaderboardPage::new(request(LeaderboardView::Page(3)), &ranked, "");
+        assert!(page.entries.is_empty());
+    }
+
+    #[test]
+    fn around_me_stays_on_the_board() {
+        let ranked = ranked(25);
+        let page = LeaderboardPage::new(request(LeaderboardView::AroundMe), &ranked, "p01");
+        assert_eq!(page.entries.len(), LEADERBOARD_PAGE_SIZE);
+        assert_eq!(page.entries[0].rank, 1);
+        assert_eq!(page.own.map(|entry| entry.rank), Some(2));
+        let page = LeaderboardPage::new(request(LeaderboardView::AroundMe), &ranked, "p12");
+        assert_eq!(page.entries[0].rank, 8);
+        let page = LeaderboardPage::new(request(LeaderboardView::AroundMe), &ranked, "p24");
+        assert_eq!(page.entries.last().map(|entry| entry.rank), Some(25));
+        assert_eq!(page.entries.len(), LEADERBOARD_PAGE_SIZE);
+        let ranked = ranked[..3].to_vec();
+        let page = LeaderboardPage::new(request(LeaderboardView::AroundMe), &ranked, "p02");
+        assert_eq!(page.entries.len(), 3);
+    }
+}
@@ -1,11 +1,23 @@
 use super::*;
 
+mod leaderboard;
+mod names;
+mod stats;
+mod tournament;
 mod track;
+mod validation;
 
+pub use leaderboard::*;
+pub use names::*;
+pub use stats::*;
+pub use tournament::*;
 pub use track::*;
+pub use validation::*;
 
 pub type Id = i64;
 
+pub const MAX_NAME_LEN: usize = 15;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct AvalancheConfig {
     pub min_speed: f32,
@@ -45,6 +57,9 @@ pub struct SharedModel {
     pub next_id: Id,
     #[diff(mode = "eq")]
     pub config: Config,
+    #[diff(mode = "eq")]
+    #[serde(default)]
+    pub preset: Option<String>,
     pub avalanche_position: Option<f32>,
     pub avalanche_speed: f32,
     pub players: Collection<Player>,
@@ -53,24 +68,96 @@ pub struct SharedModel {
     #[diff(mode = "eq")]
     pub winner: Option<(String, i32)>,
     #[diff(mode = "eq")]
-    pub highscores: HashMap<String, i32>,
-    #[diff(mode = "eq")]
     pub scores: HashMap<String, i32>,
+    /// Ratings of the rated players currently on the server
+    #[diff(mode = "eq")]
+    #[serde(default)]
+    pub ratings: HashMap<String, i32>,
+    #[diff(mode = "eq")]
+    #[serde(default)]
+    pub tournament: Option<Tournament>,
     pub reset_timer: f32,
 }
 
+/// What the client speaks, the server implements the protocol on its model instead
+#[cfg(feature = "client")]
+pub enum Protocol {}
+
+#[cfg(feature = "client")]
+impl simple_net::Protocol for Protocol {
+    type SharedState = SharedModel;
+    type PlayerId = Id;
+    type Message = Message;
+    type Event = Event;
+    type Handshake = Handshake;
+    fn reconnect_handshake(requested: &mut Handshake, accepted: &Handshake) {
+        if accepted.token.is_some() {
+            requested.token = accepted.token.clone();
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Message {
     UpdatePlayer(Player),
     Score(i32),
     StartTheRace,
     Disconnect,
+    RequestLeaderboard(LeaderboardRequest),
+    RequestProfile(String),
+    /// A finished season by number, None for the latest one
+    RequestSeason(Option<usize>),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub enum Event {}
+pub enum Event {
+    Announcement(String),
+    NameRefused {
+        player_id: Id,
+        reason: String,
+    },
+    Leaderboard {
+        player_id: Id,
+        page: LeaderboardPage,
+    },
+    Profile {
+        player_id: Id,
+        profile: Profile,
+    },
+    Season {
+        player_id: Id,
+        /// None if no season has finished yet
+        season: Option<SeasonSummary>,
+    },
+}
+
+impl Event {
+    pub fn recipient(&self) -> Option<&Id> {
+        match self {
+            Self::Announcement(_) => None,
+            Self::NameRefused { player_id, .. }
+            | Self::Leaderboard { player_id, .. }
+            | Self::Profile { player_id, .. }
+            | Self::Season { player_id, .. } => Some(player_id),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum Role {
+    Player,
+    Spectator,
+}
 
-pub const TICKS_PER_SECOND: f32 = 10.0;
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Handshake {
+    pub name: String,
+    pub skin: Option<skin::Config>,
+    pub role: Role,
+    pub room: Option<String>,
+    /// Proves ownership of claimed names, issued by the server on first connect
+    pub token: Option<String>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
 pub enum PlayerState {
@@ -84,11 +171,13 @@ pub enum PlayerState {
         ski_velocity: vec2<f32>,
         ski_rotation: Angle<f32>,
         crash_position: vec2<f32>,
+        cause: CrashCause,
     },
     Parachute {
         timer: f32,
     },
 }
+#[cfg(feature = "client")]
 impl PlayerState {
     pub fn can_crash(&self, config: &Config) -> bool {
         match self {
@@ -118,6 +207,7 @@ pub struct Player {
     pub ride_volume: f32,
 }
 
+#[cfg(feature = "client")]
 impl Player {
     pub fn score(&self) -> i32 {
         ((self.start_y - self.position.y) * 100.0) as i32
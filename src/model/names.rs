@@ -0,0 +1,114 @@
+use super::*;
+
+/// Everything the bitmap font can draw, names may only use these
+pub const NAME_CHARS: &str = "abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Names that would be mistaken for the server or the game itself
+const RESERVED_NAMES: &[&str] = &["admin", "server", "system", "moderator"];
+
+const PROFANITY: &str = include_str!("../../assets/profanity.txt");
+const PROFANITY_ALLOWLIST: &str = include_str!("../../assets/profanity_allowlist.txt");
+
+fn word_list(list: &str) -> impl Iterator<Item = &str> {
+    list.lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty() && !word.starts_with('#'))
+}
+
+/// Reduce a name to the characters that are actually drawn,
+/// so names that look the same are the same
+pub fn normalize_name(name: &str) -> String {
+    name.chars()
+        .flat_map(char::to_lowercase)
+        .filter(|&c| NAME_CHARS.contains(c))
+        .take(MAX_NAME_LEN)
+        .collect()
+}
+
+fn undo_leetspeak(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' => 'i',
+            '3' => 'e',
+            '4' => 'a',
+            '5' => 's',
+            '7' => 't',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Whether the name contains a profane word once the allowed words are cut out of it
+fn is_profane(name: &str) -> bool {
+    let mut name = name.to_owned();
+    for word in word_list(PROFANITY_ALLOWLIST) {
+        // Cut with a separator so the remaining parts can't join into a new word
+        name = name.replace(word, " ");
+    }
+    word_list(PROFANITY).any(|word| name.contains(word))
+}
+
+/// Check a normalized name, the empty name is allowed and means unnamed.
+/// Errors are lowercase so they can be drawn with the game font
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(format!("name is longer than {} letters", MAX_NAME_LEN));
+    }
+    if !name.chars().all(|c| NAME_CHARS.contains(c)) {
+        return Err("name can only use letters and digits".to_owned());
+    }
+    if RESERVED_NAMES.contains(&name) {
+        return Err(format!("name {} is reserved", name));
+    }
+    if is_profane(name) || is_profane(&undo_leetspeak(name)) {
+        return Err("name is not allowed".to_owned());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordinary_names_are_allowed() {
+        for name in [
+            "parser",
+            "grape",
+            "peacock",
+            "cockpit",
+            "canal",
+            "analyst",
+            "janus",
+            "dickens",
+            "scunthorpe",
+            "assassin",
+            "grapes4ever",
+            "scraper",
+        ] {
+            assert_eq!(validate_name(name), Ok(()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn profanity_is_refused() {
+        for name in [
+            "shit",
+            "sh1t",
+            "shits",
+            "shit99",
+            "5h1t",
+            "fag",
+            "f4g",
+            "fuckyou",
+            "bigdick",
+            "shithead",
+            "5h1thead",
+            "dickensfuck",
+            "grapecunt",
+        ] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+    }
+}
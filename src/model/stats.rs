@@ -0,0 +1,63 @@
+use super::*;
+
+/// Rating of a player who has not finished a race against anyone yet
+pub const INITIAL_RATING: f32 = 1500.0;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CrashCause {
+    Obstacle,
+    Border,
+    Avalanche,
+}
+
+#[cfg(feature = "client")]
+impl CrashCause {
+    pub const ALL: [Self; 3] = [Self::Obstacle, Self::Border, Self::Avalanche];
+    /// Profile row showing how often a player crashed this way
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Obstacle => "tree and rock crashes",
+            Self::Border => "border crashes",
+            Self::Avalanche => "caught by avalanche",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PlayerStats {
+    pub races_started: u64,
+    pub races_finished: u64,
+    /// In meters, the same units as the score divided by 100
+    pub total_distance: f32,
+    pub total_score: i64,
+    pub crashes: HashMap<CrashCause, u64>,
+    pub best_place: Option<usize>,
+    /// None until the first race with other players
+    #[serde(default)]
+    pub rating: Option<f32>,
+}
+
+impl PlayerStats {
+    #[cfg(feature = "client")]
+    pub fn average_score(&self) -> Option<i64> {
+        if self.races_finished == 0 {
+            return None;
+        }
+        Some(self.total_score / self.races_finished as i64)
+    }
+    pub fn rating(&self) -> f32 {
+        self.rating.unwrap_or(INITIAL_RATING)
+    }
+    #[cfg(feature = "client")]
+    pub fn crashes(&self, cause: CrashCause) -> u64 {
+        self.crashes.get(&cause).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub stats: PlayerStats,
+    /// All-time best with its rank
+    pub highscore: Option<LeaderboardEntry>,
+}
@@ -0,0 +1,165 @@
+use super::*;
+
+/// Points for the first places of a heat when scoring by place
+pub const PLACE_POINTS: [i32; 10] = [25, 18, 15, 12, 10, 8, 6, 4, 2, 1];
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub enum Scoring {
+    /// Best heat score
+    Best,
+    /// Sum of heat scores
+    Sum,
+    /// Points by place in every heat
+    Points,
+}
+
+impl Scoring {
+    pub const ALL: [Self; 3] = [Self::Best, Self::Sum, Self::Points];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Best => "best",
+            Self::Sum => "sum",
+            Self::Points => "points",
+        }
+    }
+}
+
+impl std::str::FromStr for Scoring {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|scoring| scoring.name() == s)
+            .ok_or_else(|| format!("Unknown scoring {:?}, expected best, sum or points", s))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Tournament {
+    pub name: String,
+    /// Every heat is raced on the track generated from this seed
+    pub seed: u64,
+    pub heats: usize,
+    pub scoring: Scoring,
+    pub participants: Vec<String>,
+    /// The next race to start counts as a heat
+    #[serde(default)]
+    pub armed: bool,
+    /// Scores of the participants who finished, one map per completed heat
+    pub results: Vec<HashMap<String, i32>>,
+}
+
+impl Tournament {
+    pub fn is_finished(&self) -> bool {
+        self.results.len() >= self.heats
+    }
+    fn heat_points(heat: &HashMap<String, i32>, name: &str) -> i32 {
+        let score = match heat.get(name) {
+            Some(&score) => score,
+            None => return 0,
+        };
+        // Players with equal scores share a place
+        let place = heat.values().filter(|&&other| other > score).count();
+        PLACE_POINTS.get(place).copied().unwrap_or(0)
+    }
+    pub fn total(&self, name: &str) -> i32 {
+        let scores = self.results.iter().map(|heat| heat.get(name).copied());
+        match self.scoring {
+            Scoring::Best => scores.flatten().max().unwrap_or(0),
+            Scoring::Sum => scores.flatten().sum(),
+            Scoring::Points => self
+                .results
+                .iter()
+                .map(|heat| Self::heat_points(heat, name))
+                .sum(),
+        }
+    }
+    pub fn standings(&self) -> Vec<LeaderboardEntry> {
+        let totals: HashMap<String, i32> = self
+            .participants
+            .iter()
+            .map(|name| (name.clone(), self.total(name)))
+            .collect();
+        rank_scores(&totals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tournament(scoring: Scoring, results: &[&[(&str, i32)]]) -> Tournament {
+        Tournament {
+            name: "cup".to_owned(),
+            seed: 0,
+            heats: 3,
+            scoring,
+            participants: ["a", "b", "c", "d"].map(str::to_owned).to_vec(),
+            armed: false,
+            results: results
+                .iter()
+                .map(|heat| {
+                    heat.iter()
+                        .map(|&(name, score)| (name.to_owned(), score))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    fn standings(tournament: &Tournament) -> Vec<(usize, String, i32)> {
+        tournament
+            .standings()
+            .into_iter()
+            .map(|entry| (entry.rank, entry.name, entry.score))
+            .collect()
+    }
+
+    #[test]
+    fn equal_heat_scores_share_a_place() {
+        let tournament = tournament(
+            Scoring::Points,
+            &[
+                &[("a", 100), ("b", 100), ("c", 50)],
+                &[("c", 200), ("a", 10)],
+            ],
+        );
+        assert_eq!(tournament.total("a"), 25 + 18);
+        assert_eq!(tournament.total("b"), 25);
+        assert_eq!(tournament.total("c"), 15 + 25);
+        assert_eq!(tournament.total("d"), 0);
+        assert!(!tournament.is_finished());
+    }
+
+    #[test]
+    fn standings_rank_every_participant() {
+        let results: &[&[(&str, i32)]] = &[&[("a", 30), ("b", 50)], &[("a", 40), ("c", 20)]];
+        assert_eq!(
+            standings(&tournament(Scoring::Sum, results)),
+            [
+                (1, "a".to_owned(), 70),
+                (2, "b".to_owned(), 50),
+                (3, "c".to_owned(), 20),
+                (4, "d".to_owned(), 0),
+            ]
+        );
+        assert_eq!(
+            standings(&tournament(Scoring::Best, results)),
+            [
+                (1, "b".to_owned(), 50),
+                (2, "a".to_owned(), 40),
+                (3, "c".to_owned(), 20),
+                (4, "d".to_owned(), 0),
+            ]
+        );
+        assert_eq!(
+            standings(&tournament(Scoring::Points, results)),
+            [
+                (1, "a".to_owned(), 18 + 25),
+                (2, "b".to_owned(), 25),
+                (3, "c".to_owned(), 18),
+                (4, "d".to_owned(), 0),
+            ]
+        );
+    }
+}
@@ -13,6 +13,15 @@ pub struct TrackConfig {
     pub curve_exp: f32,
 }
 
+#[derive(Deserialize, Clone)]
+#[cfg_attr(feature = "client", derive(geng::asset::Load))]
+#[cfg_attr(feature = "client", load(serde = "json"))]
+pub struct ObstacleConfig {
+    pub hitbox_origin: vec2<f32>,
+    pub hitbox_radius: f32,
+    pub spawn_weight: f32,
+}
+
 #[derive(Debug, Serialize, Deserialize, Diff, Clone, PartialEq)]
 pub struct Obstacle {
     pub index: usize,
@@ -31,6 +40,7 @@ pub struct ShapePoint {
     pub safe_right: f32,
 }
 
+#[cfg(feature = "client")]
 impl ShapePoint {
     pub fn middle(&self) -> f32 {
         (self.safe_left + self.safe_right) / 2.0
@@ -44,6 +54,7 @@ pub struct Track {
 }
 
 impl Track {
+    #[cfg(feature = "client")]
     pub fn query_obstacles(&self, start: f32, end: f32) -> &[Obstacle] {
         let start = match self
             .obstacles
@@ -61,6 +72,7 @@ impl Track {
         };
         &self.obstacles[start..end]
     }
+    #[cfg(feature = "client")]
     pub fn query_shape(&self, start: f32, end: f32) -> &[ShapePoint] {
         let start = match self.shape.binary_search_by_key(&r32(-start), |p| -r32(p.y)) {
             Ok(idx) => idx,
@@ -0,0 +1,190 @@
+use super::*;
+
+use std::ops::{Bound, RangeBounds};
+
+#[derive(Debug)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Default)]
+pub struct Validator {
+    path: Vec<String>,
+    errors: Vec<ValidationError>,
+}
+
+fn format_range(range: &impl RangeBounds<f32>) -> String {
+    let start = match range.start_bound() {
+        Bound::Included(x) => format!("[{}", x),
+        Bound::Excluded(x) => format!("({}", x),
+        Bound::Unbounded => "(-inf".to_owned(),
+    };
+    let end = match range.end_bound() {
+        Bound::Included(x) => format!("{}]", x),
+        Bound::Excluded(x) => format!("{})", x),
+        Bound::Unbounded => "inf)".to_owned(),
+    };
+    format!("{}, {}", start, end)
+}
+
+impl Validator {
+    fn path_to(&self, name: &str) -> String {
+        let mut path = self.path.join(".");
+        if !name.is_empty() {
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(name);
+        }
+        path
+    }
+    pub fn error(&mut self, name: &str, message: impl Into<String>) {
+        self.errors.push(ValidationError {
+            path: self.path_to(name),
+            message: message.into(),
+        });
+    }
+    pub fn field(&mut self, name: &str, value: &impl Validate) {
+        self.path.push(name.to_owned());
+        value.validate(self);
+        self.path.pop();
+    }
+    pub fn range(&mut self, name: &str, value: f32, range: impl RangeBounds<f32>) {
+        if !value.is_finite() || !range.contains(&value) {
+            self.error(
+                name,
+                format!("must be in {}, got {}", format_range(&range), value),
+            );
+        }
+    }
+    pub fn finish(self, what: &str) -> anyhow::Result<()> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        let mut text = format!("Invalid {}:", what);
+        for error in &self.errors {
+            text.push_str("\n  ");
+            text.push_str(&error.to_string());
+        }
+        Err(anyhow::anyhow!(text))
+    }
+}
+
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+pub fn validate(value: &impl Validate, what: &str) -> anyhow::Result<()> {
+    let mut validator = Validator::default();
+    value.validate(&mut validator);
+    validator.finish(what)
+}
+
+const POSITIVE: (Bound<f32>, Bound<f32>) = (Bound::Excluded(0.0), Bound::Unbounded);
+
+impl Validate for Config {
+    fn validate(&self, v: &mut Validator) {
+        v.range("invincibility_time", self.invincibility_time, 0.0..);
+        v.field("avalanche", &self.avalanche);
+        v.field("track", &self.track);
+        v.field("player", &self.player);
+    }
+}
+
+impl Validate for AvalancheConfig {
+    fn validate(&self, v: &mut Validator) {
+        v.range("min_speed", self.min_speed, POSITIVE);
+        v.range("max_speed", self.max_speed, self.min_speed..);
+        v.range("max_speed_time", self.max_speed_time, POSITIVE);
+        v.range("start", self.start, 0.0..);
+    }
+}
+
+impl Validate for TrackConfig {
+    fn validate(&self, v: &mut Validator) {
+        v.range("width", self.width, POSITIVE);
+        v.range("safe_middle", self.safe_middle, 0.0..=self.width);
+        v.range("obstacle_density", self.obstacle_density, 0.0..=1.0);
+        v.range(
+            "distance_between_obstacles",
+            self.distance_between_obstacles,
+            0.0..,
+        );
+        v.range("spawn_area", self.spawn_area, POSITIVE);
+        v.range(
+            "spawn_width",
+            self.spawn_width,
+            (Bound::Excluded(0.0), Bound::Included(self.width)),
+        );
+        v.range("step", self.step, POSITIVE);
+        v.range("max_curve", self.max_curve, 0.0..);
+        v.range("curve_exp", self.curve_exp, POSITIVE);
+    }
+}
+
+impl Validate for PlayerConfig {
+    fn validate(&self, v: &mut Validator) {
+        v.range("rotation_speed", self.rotation_speed.as_radians(), POSITIVE);
+        v.range(
+            "rotation_limit",
+            self.rotation_limit.as_radians(),
+            (Bound::Excluded(0.0), Bound::Included(f32::PI)),
+        );
+        v.range("max_speed", self.max_speed, POSITIVE);
+        v.range("max_walk_speed", self.max_walk_speed, 0.0..);
+        v.range("friction", self.friction, 0.0..);
+        v.range("downhill_acceleration", self.downhill_acceleration, 0.0..);
+        v.range("walk_acceleration", self.walk_acceleration, 0.0..);
+        v.range("crash_deceleration", self.crash_deceleration, POSITIVE);
+        v.range("parachute_time", self.parachute_time, POSITIVE);
+    }
+}
+
+impl Validate for ObstacleConfig {
+    fn validate(&self, v: &mut Validator) {
+        if !self.hitbox_origin.x.is_finite() || !self.hitbox_origin.y.is_finite() {
+            v.error("hitbox_origin", "must be finite");
+        }
+        v.range("hitbox_radius", self.hitbox_radius, POSITIVE);
+        v.range("spawn_weight", self.spawn_weight, 0.0..);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_config() -> Config {
+        serde_json::from_str(include_str!("../../assets/config.json")).unwrap()
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        validate(&default_config(), "config").unwrap();
+    }
+
+    #[test]
+    fn every_error_is_reported_with_its_path() {
+        let mut config = default_config();
+        config.invincibility_time = -1.0;
+        config.avalanche.max_speed = 5.0;
+        config.track.obstacle_density = 2.0;
+        config.player.max_speed = f32::NAN;
+        let error = validate(&config, "config").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid config:\n  \
+             invincibility_time: must be in [0, inf), got -1\n  \
+             avalanche.max_speed: must be in [10, inf), got 5\n  \
+             track.obstacle_density: must be in [0, 1], got 2\n  \
+             player.max_speed: must be in (0, inf), got NaN"
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use super::*;
+
+type Handle = simple_net::ServerHandle<Model>;
+
+const HELP: &str = "Commands:
+  list                      - list connected players
+  kick <id|name> [reason]   - disconnect a player
+  ban <name> [reason]       - ban a name and kick its players
+  unban <name>              - lift a ban
+  ban-ip <address>          - ban an address and close its connections
+  unban-ip <address>        - lift an address ban
+  bans                      - list banned names and addresses
+  unclaim <name>            - let anyone take a claimed name
+  start                     - start the race
+  reset                     - abort the current race and generate a new track
+  reload                    - reload the config before the next race
+  preset [name]             - switch to a config preset, or back to the base config
+  announce <text>           - show a message to everyone in game
+  unscore <name>            - remove a highscore entry
+  tournament                - show the tournament table
+  tournament new <heats> <best|sum|points> [name]
+                            - start a tournament, every heat is raced on the same track
+  tournament add <name>     - register a participant
+  tournament remove <name>  - unregister a participant
+  tournament heat           - make the next race to start count as the next heat
+  tournament end            - stop the tournament
+  stop [eta] [reason]       - shut down the server
+  help                      - show this message";
+
+pub fn run_console(handle: Handle) {
+    std::thread::spawn(move || {
+        for line in std::io::BufRead::lines(std::io::stdin().lock()) {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    log::error!("Failed to read admin command: {}", e);
+                    break;
+                }
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match execute(&handle, line) {
+                Ok(output) => println!("{}", output),
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+    });
+}
+
+/// Players with the given id, or else the given name as it would be shown in game
+fn find_players(clients: &HashMap<Id, Handshake>, target: &str) -> Vec<Id> {
+    if let Ok(id) = target.parse::<Id>() {
+        if clients.contains_key(&id) {
+            return vec![id];
+        }
+    }
+    let name = normalize_name(target);
+    if name.is_empty() {
+        return vec![];
+    }
+    clients
+        .iter()
+        .filter(|(_, client)| client.name == name)
+        .map(|(&id, _)| id)
+        .collect()
+}
+
+fn kick(handle: &Handle, players: Vec<Id>, reason: &str) -> String {
+    let kicked = players.iter().filter(|id| handle.kick(id, reason)).count();
+    format!("Kicked {} player(s)", kicked)
+}
+
+fn tournament_table(tournament: &Tournament) -> String {
+    let mut text = format!(
+        "{:?}: heat {} of {}, scoring by {}, {} participant(s)",
+        tournament.name,
+        tournament.results.len(),
+        tournament.heats,
+        tournament.scoring.name(),
+        tournament.participants.len(),
+    );
+    if tournament.armed {
+        text.push_str(", the next race is a heat");
+    }
+    for entry in tournament.standings() {
+        text.push_str(&format!(
+            "\n{:>4}. {:<15} {:>7}",
+            entry.rank, entry.name, entry.score
+        ));
+        for heat in &tournament.results {
+            match heat.get(&entry.name) {
+                Some(score) => text.push_str(&format!(" {:>7}", score)),
+                None => text.push_str(&format!(" {:>7}", "-")),
+            }
+        }
+    }
+    text
+}
+
+fn tournament(handle: &Handle, args: &str) -> Result<String, String> {
+    let (command, args) = match args.split_once(' ') {
+        Some((command, args)) => (command, args.trim()),
+        None => (args, ""),
+    };
+    match command {
+        "" => handle.update(|model, _| match &model.shared.tournament {
+            Some(tournament) => Ok(tournament_table(tournament)),
+            None => Err("No tournament is running".to_owned()),
+        }),
+        "new" => {
+            const USAGE: &str = "Usage: tournament new <heats> <best|sum|points> [name]";
+            let mut parts = args.splitn(3, ' ');
+            let heats: usize = parts
+                .next()
+                .and_then(|heats| heats.parse().ok())
+                .filter(|&heats| heats > 0)
+                .ok_or(USAGE)?;
+            let scoring: Scoring = parts.next().ok_or(USAGE)?.parse()?;
+            let name = parts.next().unwrap_or("").trim();
+            handle.update(|model, _| model.new_tournament(name, heats, scoring))?;
+            Ok(format!(
+                "Tournament of {} heat(s) started, register participants with tournament add",
+                heats
+            ))
+        }
+        "heat" => {
+            let heat = handle.update(|model, _| model.arm_heat())?;
+            Ok(format!("The next race to start is heat {}", heat))
+        }
+        "add" => {
+            if args.is_empty() {
+                return Err("Usage: tournament add <name>".to_owned());
+            }
+            let name = handle.update(|model, _| model.register_participant(args))?;
+            Ok(format!("Registered {:?}", name))
+        }
+        "remove" => {
+            if handle.update(|model, _| model.unregister_participant(args)) {
+                Ok(format!("Unregistered {:?}", args))
+            } else {
+                Err(format!("{:?} is not registered", args))
+            }
+        }
+        "end" => {
+            if handle.update(|model, _| model.end_tournament()) {
+                Ok("Tournament ended".to_owned())
+            } else {
+                Err("No tournament is running".to_owned())
+            }
+        }
+        _ => Err(format!("Unknown tournament command {:?}", command)),
+    }
+}
+
+fn execute(handle: &Handle, line: &str) -> Result<String, String> {
+    let (command, args) = match line.split_once(' ') {
+        Some((command, args)) => (command, args.trim()),
+        None => (line, ""),
+    };
+    let (first, rest) = match args.split_once(' ') {
+        Some((first, rest)) => (first, rest.trim()),
+        None => (args, ""),
+    };
+    let reason = if rest.is_empty() {
+        "no reason given"
+    } else {
+        rest
+    };
+    match command {
+        "help" => Ok(HELP.to_owned()),
+        "list" => Ok(handle.update(|model, _| {
+            let mut ids: Vec<Id> = model.clients.keys().copied().collect();
+            ids.sort();
+            let mut text = format!("{} client(s) connected", ids.len());
+            for id in ids {
+                let client = &model.clients[&id];
+                text.push_str(&format!("\n{:>4} {:?} {:?}", id, client.name, client.role));
+                if let Some(score) = model.shared.scores.get(&client.name) {
+                    text.push_str(&format!(" score {}", score));
+                }
+            }
+            text
+        })),
+        "kick" => {
+            if first.is_empty() {
+                return Err("Usage: kick <id|name> [reason]".to_owned());
+            }
+            let players = handle.update(|model, _| find_players(&model.clients, first));
+            if players.is_empty() {
+                return Err(format!("No player {:?}", first));
+            }
+            Ok(kick(handle, players, reason))
+        }
+        "ban" => {
+            if first.is_empty() {
+                return Err("Usage: ban <name> [reason]".to_owned());
+            }
+            let players = handle.update(|model, _| {
+                model.ban(first);
+                find_players(&model.clients, first)
+            });
+            Ok(format!(
+                "Banned {:?}. {}",
+                first,
+                kick(handle, players, reason)
+            ))
+        }
+        "unban" => {
+            if handle.update(|model, _| model.unban(args)) {
+                Ok(format!("Unbanned {:?}", args))
+            } else {
+                Err(format!("{:?} is not banned", args))
+            }
+        }
+        "ban-ip" => {
+            let addr: std::net::IpAddr = args
+                .parse()
+                .map_err(|_| "Usage: ban-ip <address>".to_owned())?;
+            handle.update(|model, _| model.ban_address(addr));
+            let closed = handle.disconnect_address(&addr);
+            Ok(format!("Banned {}, closed {} connection(s)", addr, closed))
+        }
+        "unban-ip" => {
+            let addr: std::net::IpAddr = args
+                .parse()
+                .map_err(|_| "Usage: unban-ip <address>".to_owned())?;
+            if handle.update(|model, _| model.unban_address(&addr)) {
+                Ok(format!("Unbanned {}", addr))
+            } else {
+                Err(format!("{} is not banned", addr))
+            }
+        }
+        "bans" => Ok(handle.update(|model, _| {
+            let mut bans: Vec<&String> = model.bans.iter().collect();
+            bans.sort();
+            let mut addresses: Vec<&std::net::IpAddr> = model.address_bans.iter().collect();
+            addresses.sort();
+            format!(
+                "{} name(s) banned: {:?}\n{} address(es) banned: {:?}",
+                bans.len(),
+                bans,
+                addresses.len(),
+                addresses
+            )
+        })),
+        "unclaim" => {
+            if handle.update(|model, _| model.claims.unclaim(args)) {
+                Ok(format!("{:?} can be claimed again", args))
+            } else {
+                Err(format!("{:?} is not claimed", args))
+            }
+        }
+        "start" => {
+            handle.update(|model, _| model.start_race());
+            Ok("Race started".to_owned())
+        }
+        "reset" => {
+            handle.update(|model, _| model.reset_race());
+            Ok("Race reset".to_owned())
+        }
+        "reload" => {
+            handle
+                .update(|model, _| model.reload_config())
+                .map_err(|e| format!("{:#}", e))?;
+            Ok("Config reloaded, it will be applied before the next race".to_owned())
+        }
+        "preset" => {
+            let preset = if args.is_empty() {
+                None
+            } else {
+                Some(args.to_owned())
+            };
+            handle
+                .update(|model, _| model.set_preset(preset))
+                .map_err(|e| format!("{:#}", e))?;
+            Ok("Preset will be applied before the next race".to_owned())
+        }
+        "announce" => {
+            if args.is_empty() {
+                return Err("Usage: announce <text>".to_owned());
+            }
+            handle.update(|_, events| events.push(Event::Announcement(args.to_owned())));
+            Ok("Announced".to_owned())
+        }
+        "unscore" => {
+            if handle
+                .update(|model, _| model.remove_highscore(args))
+                .map_err(|e| format!("{:#}", e))?
+            {
+                Ok(format!("Removed highscore of {:?}", args))
+            } else {
+                Err(format!("{:?} has no highscore", args))
+            }
+        }
+        "tournament" => tournament(handle, args),
+        "stop" => {
+            let (eta, reason) = match first.parse::<f32>() {
+                Ok(eta) => (Some(eta), rest),
+                Err(_) => (None, args),
+            };
+            let reason = if reason.is_empty() {
+                None
+            } else {
+                Some(reason.to_owned())
+            };
+            handle.shutdown(reason, eta);
+            Ok("Server stopped".to_owned())
+        }
+        _ => Err(format!(
+            "Unknown command {:?}, type help for the list",
+            command
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(name: &str) -> Handshake {
+        Handshake {
+            name: name.to_owned(),
+            skin: None,
+            role: Role::Player,
+            room: None,
+            token: None,
+        }
+    }
+
+    #[test]
+    fn players_are_found_by_normalized_name() {
+        let clients: HashMap<Id, Handshake> =
+            [(1, client("bob")), (2, client("")), (3, client("bob"))]
+                .into_iter()
+                .collect();
+        let mut found = find_players(&clients, "Bob!");
+        found.sort();
+        assert_eq!(found, vec![1, 3]);
+        assert_eq!(find_players(&clients, "2"), vec![2]);
+        assert_eq!(find_players(&clients, "!!"), Vec::<Id>::new());
+        assert_eq!(find_players(&clients, "alice"), Vec::<Id>::new());
+    }
+}
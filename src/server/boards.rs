@@ -0,0 +1,198 @@
+use super::*;
+
+const DAY: u64 = 24 * 60 * 60;
+const WEEK: u64 = 7 * DAY;
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// When the periodic boards start over, in UTC
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rollover {
+    /// Seconds after midnight
+    pub time: u64,
+    /// Day of the week the weekly board starts on, 0 is monday
+    pub weekday: u64,
+}
+
+pub fn parse_time_of_day(s: &str) -> Result<u64, String> {
+    let (hours, minutes) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Expected HH:MM, got {:?}", s))?;
+    let hours: u64 = hours.parse().map_err(|e| format!("Bad hours: {}", e))?;
+    let minutes: u64 = minutes.parse().map_err(|e| format!("Bad minutes: {}", e))?;
+    if hours >= 24 || minutes >= 60 {
+        return Err(format!("{:?} is not a time of day", s));
+    }
+    Ok((hours * 60 + minutes) * 60)
+}
+
+pub fn parse_weekday(s: &str) -> Result<u64, String> {
+    let s = s.to_lowercase();
+    WEEKDAYS
+        .iter()
+        .position(|day| day.starts_with(&s) && s.len() >= 3)
+        .map(|index| index as u64)
+        .ok_or_else(|| format!("Unknown day of the week {:?}", s))
+}
+
+impl Rollover {
+    /// Start of the period containing the given unix time, None for the all-time board
+    pub fn period_start(&self, board: Board, time: u64) -> Option<u64> {
+        fn start(time: u64, anchor: u64, length: u64) -> u64 {
+            let offset = (time as i64 - anchor as i64).div_euclid(length as i64);
+            (anchor as i64 + offset * length as i64) as u64
+        }
+        match board {
+            Board::Daily => Some(start(time, self.time, DAY)),
+            // Unix time starts on a thursday
+            Board::Weekly => Some(start(time, self.time + (self.weekday + 4) % 7 * DAY, WEEK)),
+            Board::AllTime | Board::Rating => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeriodBoard {
+    pub start: u64,
+    pub scores: HashMap<String, i32>,
+}
+
+pub struct Boards {
+    pub rollover: Rollover,
+    pub periods: HashMap<Board, PeriodBoard>,
+}
+
+impl Boards {
+    pub fn path() -> std::path::PathBuf {
+        state_path("BOARDS", "boards.json")
+    }
+    pub fn load(rollover: Rollover) -> anyhow::Result<Self> {
+        let mut boards = Self {
+            rollover,
+            periods: read_json_or_default(&Self::path())?,
+        };
+        let mut moved = false;
+        for period in boards.periods.values_mut() {
+            moved |= normalize_keys(&mut period.scores, |score, other| {
+                *score = (*score).max(other)
+            });
+        }
+        if moved {
+            boards.save();
+        }
+        boards.roll_over();
+        Ok(boards)
+    }
+    fn save(&self) {
+        if let Err(e) = write_json_atomic(&Self::path(), &self.periods) {
+            log::error!("Failed to save leaderboards: {:#}", e);
+        }
+    }
+    /// Start over the boards whose period has ended
+    pub fn roll_over(&mut self) {
+        let now = unix_time();
+        let mut changed = false;
+        for board in [Board::Daily, Board::Weekly] {
+            let start = self.rollover.period_start(board, now).unwrap();
+            let period = self.periods.entry(board).or_default();
+            if period.start != start {
+                if !period.scores.is_empty() {
+                    log::info!("Starting a new {:?} leaderboard", board);
+                }
+                *period = PeriodBoard {
+                    start,
+                    scores: default(),
+                };
+                changed = true;
+            }
+        }
+        if changed {
+            self.save();
+        }
+    }
+    /// Record a score on every periodic board it improves
+    pub fn submit(&mut self, name: &str, score: i32) {
+        let mut changed = false;
+        for period in self.periods.values_mut() {
+            if score > period.scores.get(name).copied().unwrap_or(0) {
+                period.scores.insert(name.to_owned(), score);
+                changed = true;
+            }
+        }
+        if changed {
+            self.save();
+        }
+    }
+    pub fn remove(&mut self, name: &str) {
+        for period in self.periods.values_mut() {
+            period.scores.remove(name);
+        }
+        self.save();
+    }
+    /// Empty every periodic board without moving its period
+    pub fn clear(&mut self) {
+        for period in self.periods.values_mut() {
+            period.scores.clear();
+        }
+        self.save();
+    }
+    pub fn scores(&self, board: Board) -> Option<&HashMap<String, i32>> {
+        self.periods.get(&board).map(|period| &period.scores)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn daily_boards_roll_over_at_the_configured_time() {
+        let rollover = Rollover {
+            time: parse_time_of_day("06:00").unwrap(),
+            weekday: 0,
+        };
+        let boundary = 100 * DAY + 6 * 60 * 60;
+        assert_eq!(
+            rollover.period_start(Board::Daily, boundary),
+            Some(boundary)
+        );
+        assert_eq!(
+            rollover.period_start(Board::Daily, boundary - 1),
+            Some(boundary - DAY)
+        );
+        assert_eq!(
+            rollover.period_start(Board::Daily, boundary + DAY - 1),
+            Some(boundary)
+        );
+        assert_eq!(rollover.period_start(Board::AllTime, boundary), None);
+    }
+
+    #[test]
+    fn weekly_boards_roll_over_on_the_configured_day() {
+        let rollover = Rollover {
+            time: parse_time_of_day("06:00").unwrap(),
+            weekday: parse_weekday("sunday").unwrap(),
+        };
+        // 1970-01-04 was a sunday
+        let boundary = 3 * DAY + 6 * 60 * 60 + 10 * WEEK;
+        assert_eq!(
+            rollover.period_start(Board::Weekly, boundary),
+            Some(boundary)
+        );
+        assert_eq!(
+            rollover.period_start(Board::Weekly, boundary - 1),
+            Some(boundary - WEEK)
+        );
+        assert_eq!(
+            rollover.period_start(Board::Weekly, boundary + WEEK - 1),
+            Some(boundary)
+        );
+    }
+}
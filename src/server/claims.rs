@@ -0,0 +1,58 @@
+use super::*;
+
+/// Names that stand for "no name" and are never claimed
+const UNCLAIMABLE: &[&str] = &["", "unnamed"];
+
+pub fn new_token() -> String {
+    let mut rng = thread_rng();
+    format!("{:016x}{:016x}", rng.gen::<u64>(), rng.gen::<u64>())
+}
+
+pub struct Claims {
+    path: std::path::PathBuf,
+    tokens: HashMap<String, String>,
+}
+
+impl Claims {
+    pub fn path() -> std::path::PathBuf {
+        state_path("CLAIMS", "claims.json")
+    }
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path();
+        let mut tokens = read_json_or_default(&path)?;
+        // A name claimed under its normalized form stays with its owner
+        let moved = normalize_keys(&mut tokens, |_, _| {});
+        let claims = Self { path, tokens };
+        if moved {
+            claims.save();
+        }
+        Ok(claims)
+    }
+    pub fn allows(&self, name: &str, token: Option<&str>) -> bool {
+        match self.tokens.get(name) {
+            Some(owner) => Some(owner.as_str()) == token,
+            None => true,
+        }
+    }
+    /// Claim the name for the token unless someone already did
+    pub fn claim(&mut self, name: &str, token: &str) {
+        if UNCLAIMABLE.contains(&name) || self.tokens.contains_key(name) {
+            return;
+        }
+        self.tokens.insert(name.to_owned(), token.to_owned());
+        log::info!("{:?} is now claimed", name);
+        self.save();
+    }
+    pub fn unclaim(&mut self, name: &str) -> bool {
+        let removed = self.tokens.remove(&normalize_name(name)).is_some();
+        if removed {
+            self.save();
+        }
+        removed
+    }
+    fn save(&self) {
+        if let Err(e) = write_json_atomic(&self.path, &self.tokens) {
+            log::error!("Failed to save name claims: {:#}", e);
+        }
+    }
+}
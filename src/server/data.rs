@@ -0,0 +1,155 @@
+use super::*;
+use anyhow::Context as _;
+
+macro_rules! embed {
+    ($($name:literal),* $(,)?) => {
+        &[$(($name, include_str!(concat!("../../assets/", $name)))),*]
+    };
+}
+
+const EMBEDDED: &[(&str, &str)] = embed![
+    "config.json",
+    "presets.json",
+    "obstacles.json",
+    "tree.json",
+    "tree2.json",
+    "tree3.json",
+    "tree4.json",
+    "tree5.json",
+    "tree6.json",
+    "stone.json",
+    "stone2.json",
+    "flag.json",
+];
+
+/// Path set by the environment variable, or the default
+pub fn state_path(env: &str, default: impl Into<std::path::PathBuf>) -> std::path::PathBuf {
+    match std::env::var(env) {
+        Ok(path) => path.into(),
+        Err(_) => default.into(),
+    }
+}
+
+pub fn data_dir() -> std::path::PathBuf {
+    state_path("DATA_DIR", assets_path())
+}
+
+fn embedded(path: &std::path::Path) -> Option<&'static str> {
+    // A path pointing anywhere else was chosen on purpose and has to exist
+    if path.parent()? != assets_path() {
+        return None;
+    }
+    let name = path.file_name()?.to_str()?;
+    EMBEDDED
+        .iter()
+        .find(|(embedded_name, _)| *embedded_name == name)
+        .map(|(_, data)| *data)
+}
+
+pub fn read_data(path: &std::path::Path) -> anyhow::Result<std::borrow::Cow<'static, str>> {
+    if path.is_file() {
+        let data =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        return Ok(data.into());
+    }
+    match embedded(path) {
+        Some(data) => Ok(data.into()),
+        None => anyhow::bail!("{:?} does not exist and has no embedded default", path),
+    }
+}
+
+pub fn write_json_atomic(path: &std::path::Path, value: &impl Serialize) -> anyhow::Result<()> {
+    // Appending keeps files that only differ by extension from sharing a temporary file
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = std::path::PathBuf::from(tmp_path);
+    {
+        let mut writer = std::io::BufWriter::new(
+            std::fs::File::create(&tmp_path)
+                .with_context(|| format!("Failed to create {:?}", tmp_path))?,
+        );
+        serde_json::to_writer(&mut writer, value)?;
+        std::io::Write::flush(&mut writer)?;
+        writer
+            .get_ref()
+            .sync_all()
+            .with_context(|| format!("Failed to sync {:?}", tmp_path))?;
+    }
+    std::fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {:?}", path))?;
+    Ok(())
+}
+
+/// Move entries stored under names from before normalization to their normalized names,
+/// merging them into entries already there. Returns whether anything moved
+pub fn normalize_keys<V>(map: &mut HashMap<String, V>, mut merge: impl FnMut(&mut V, V)) -> bool {
+    let stale: Vec<String> = map
+        .keys()
+        .filter(|name| normalize_name(name) != **name)
+        .cloned()
+        .collect();
+    for name in &stale {
+        let value = map.remove(name).unwrap();
+        match map.entry(normalize_name(name)) {
+            std::collections::hash_map::Entry::Occupied(mut entry) => merge(entry.get_mut(), value),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+    }
+    !stale.is_empty()
+}
+
+pub fn read_json<T: serde::de::DeserializeOwned>(path: &std::path::Path) -> anyhow::Result<T> {
+    serde_json::from_str(&read_data(path)?).with_context(|| format!("Failed to parse {:?}", path))
+}
+
+/// Read state the server keeps itself, starting from the default before the first save
+pub fn read_json_or_default<T: serde::de::DeserializeOwned + Default>(
+    path: &std::path::Path,
+) -> anyhow::Result<T> {
+    if !path.is_file() {
+        return Ok(T::default());
+    }
+    let file = std::fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("Failed to parse {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_obstacles_match_the_list() {
+        let list: Vec<String> =
+            serde_json::from_str(embedded(&assets_path().join("obstacles.json")).unwrap()).unwrap();
+        let mut expected: Vec<String> = ["config.json", "presets.json", "obstacles.json"]
+            .into_iter()
+            .map(String::from)
+            .chain(list.iter().map(|name| format!("{}.json", name)))
+            .collect();
+        let mut names: Vec<String> = EMBEDDED.iter().map(|(name, _)| name.to_string()).collect();
+        expected.sort();
+        names.sort();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn missing_state_reads_as_default() {
+        let path = std::env::temp_dir().join(format!("state-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let missing: HashMap<String, i32> = read_json_or_default(&path).unwrap();
+        assert!(missing.is_empty());
+        let saved: HashMap<String, i32> = [("alice".to_owned(), 3)].into_iter().collect();
+        write_json_atomic(&path, &saved).unwrap();
+        let loaded: HashMap<String, i32> = read_json_or_default(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, saved);
+    }
+
+    #[test]
+    fn embedded_only_in_default_dir() {
+        assert!(embedded(&assets_path().join("config.json")).is_some());
+        assert!(embedded(std::path::Path::new("/nonexistent/config.json")).is_none());
+    }
+}
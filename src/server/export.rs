@@ -0,0 +1,160 @@
+use super::*;
+use anyhow::Context as _;
+
+struct Table {
+    /// File name without the extension, also used as the html anchor
+    id: String,
+    title: String,
+    entries: Vec<LeaderboardEntry>,
+}
+
+fn best_scores<'a>(scores: impl IntoIterator<Item = (&'a str, i32)>) -> HashMap<String, i32> {
+    let mut best = HashMap::<String, i32>::new();
+    for (name, score) in scores {
+        let entry = best.entry(name.to_owned()).or_insert(score);
+        *entry = (*entry).max(score);
+    }
+    best
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_csv(path: &std::path::Path, entries: &[LeaderboardEntry]) -> anyhow::Result<()> {
+    let mut text = String::from("rank,name,score\n");
+    for entry in entries {
+        text.push_str(&format!(
+            "{},{},{}\n",
+            entry.rank,
+            csv_field(&entry.name),
+            entry.score
+        ));
+    }
+    std::fs::write(path, text).with_context(|| format!("Failed to write {:?}", path))
+}
+
+fn html(tables: &[Table]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Standings</title>\n\
+         <style>\n\
+         body { font-family: sans-serif; max-width: 40em; margin: auto; }\n\
+         table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }\n\
+         td, th { padding: 0.2em 0.5em; text-align: left; }\n\
+         td:first-child, td:last-child, th:last-child { text-align: right; }\n\
+         tr:nth-child(even) { background: #eee; }\n\
+         </style>\n</head>\n<body>\n<h1>Standings</h1>\n<ul>\n",
+    );
+    for table in tables {
+        html.push_str(&format!(
+            "<li><a href=\"#{id}\">{title}</a> (<a href=\"{id}.csv\">csv</a>)</li>\n",
+            id = table.id,
+            title = html_escape(&table.title),
+        ));
+    }
+    html.push_str("</ul>\n");
+    for table in tables {
+        html.push_str(&format!(
+            "<h2 id=\"{}\">{}</h2>\n<table>\n<tr><th>#</th><th>Name</th><th>Score</th></tr>\n",
+            table.id,
+            html_escape(&table.title),
+        ));
+        for entry in &table.entries {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                entry.rank,
+                html_escape(&entry.name),
+                entry.score,
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Write standings from the stored highscores, season archives and race results
+/// as an html page with a csv file per table
+pub fn export(dir: &std::path::Path) -> anyhow::Result<()> {
+    let highscores = open_highscores()?.load_all()?;
+    let archives = Seasons::load_archives()?;
+    let races = RaceRecord::load_all()?;
+
+    let mut tables = Vec::new();
+    let all_time = best_scores(
+        highscores
+            .iter()
+            .map(|record| (record.name.as_str(), record.score))
+            .chain(archives.iter().flat_map(|archive| {
+                archive
+                    .highscores
+                    .iter()
+                    .map(|entry| (entry.name.as_str(), entry.score))
+            })),
+    );
+    tables.push(Table {
+        id: "all-time".to_owned(),
+        title: "All time".to_owned(),
+        entries: rank_scores(&all_time),
+    });
+
+    let current = best_scores(
+        highscores
+            .iter()
+            .map(|record| (record.name.as_str(), record.score)),
+    );
+    tables.push(Table {
+        id: format!("season-{}", archives.len() + 1),
+        title: format!("Season {} (current)", archives.len() + 1),
+        entries: rank_scores(&current),
+    });
+    for archive in archives.iter().rev() {
+        tables.push(Table {
+            id: format!("season-{}", archive.number),
+            title: format!(
+                "Season {} ({} to {})",
+                archive.number,
+                format_date(archive.start),
+                format_date(archive.end),
+            ),
+            entries: archive.highscores.clone(),
+        });
+    }
+
+    let mut days = std::collections::BTreeMap::<String, Vec<(&str, i32)>>::new();
+    for race in &races {
+        days.entry(format_date(race.end_time)).or_default().extend(
+            race.results
+                .iter()
+                .map(|result| (result.name.as_str(), result.score)),
+        );
+    }
+    for (date, scores) in days.into_iter().rev() {
+        tables.push(Table {
+            id: format!("day-{}", date),
+            title: date,
+            entries: rank_scores(&best_scores(scores)),
+        });
+    }
+
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    for table in &tables {
+        write_csv(&dir.join(format!("{}.csv", table.id)), &table.entries)?;
+    }
+    let path = dir.join("index.html");
+    std::fs::write(&path, html(&tables)).with_context(|| format!("Failed to write {:?}", path))?;
+    println!("Exported {} tables to {:?}", tables.len(), dir);
+    Ok(())
+}
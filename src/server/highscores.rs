@@ -0,0 +1,202 @@
+use super::*;
+#[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+use anyhow::Context as _;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighscoreRecord {
+    pub name: String,
+    pub score: i32,
+    pub time: u64,
+    pub seed: u64,
+    pub preset: Option<String>,
+}
+
+pub trait HighscoreStore: Send {
+    fn load_all(&mut self) -> anyhow::Result<Vec<HighscoreRecord>>;
+    fn put(&mut self, record: &HighscoreRecord) -> anyhow::Result<()>;
+    fn remove(&mut self, name: &str) -> anyhow::Result<bool>;
+    /// Remove every record at once
+    fn clear(&mut self) -> anyhow::Result<()>;
+}
+
+pub struct JsonStore {
+    path: std::path::PathBuf,
+    records: std::collections::BTreeMap<String, HighscoreRecord>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonFormat {
+    Records(Vec<HighscoreRecord>),
+    /// Plain name to score map written by older versions
+    Legacy(HashMap<String, i32>),
+}
+
+impl JsonStore {
+    pub fn open(path: std::path::PathBuf) -> anyhow::Result<Self> {
+        let mut records = std::collections::BTreeMap::new();
+        if path.is_file() {
+            let data: JsonFormat = read_json(&path)?;
+            match data {
+                JsonFormat::Records(list) => {
+                    for record in list {
+                        records.insert(record.name.clone(), record);
+                    }
+                }
+                JsonFormat::Legacy(scores) => {
+                    for (name, score) in scores {
+                        records.insert(
+                            name.clone(),
+                            HighscoreRecord {
+                                name,
+                                score,
+                                time: 0,
+                                seed: 0,
+                                preset: None,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        Ok(Self { path, records })
+    }
+    fn save(&self) -> anyhow::Result<()> {
+        write_json_atomic(&self.path, &self.records.values().collect::<Vec<_>>())
+    }
+}
+
+impl HighscoreStore for JsonStore {
+    fn load_all(&mut self) -> anyhow::Result<Vec<HighscoreRecord>> {
+        Ok(self.records.values().cloned().collect())
+    }
+    fn put(&mut self, record: &HighscoreRecord) -> anyhow::Result<()> {
+        let old = self.records.insert(record.name.clone(), record.clone());
+        if let Err(e) = self.save() {
+            match old {
+                Some(old) => self.records.insert(old.name.clone(), old),
+                None => self.records.remove(&record.name),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+    fn remove(&mut self, name: &str) -> anyhow::Result<bool> {
+        let old = match self.records.remove(name) {
+            Some(old) => old,
+            None => return Ok(false),
+        };
+        if let Err(e) = self.save() {
+            self.records.insert(old.name.clone(), old);
+            return Err(e);
+        }
+        Ok(true)
+    }
+    fn clear(&mut self) -> anyhow::Result<()> {
+        let old = mem::take(&mut self.records);
+        if let Err(e) = self.save() {
+            self.records = old;
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+pub struct SledStore {
+    db: sled::Db,
+}
+
+#[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+impl SledStore {
+    /// Open the database, importing the json highscores when it is created
+    pub fn open(path: &std::path::Path, import: &std::path::Path) -> anyhow::Result<Self> {
+        let db = sled::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let mut store = Self { db };
+        if !store.db.was_recovered() && import.is_file() {
+            let records = JsonStore::open(import.to_owned())?.load_all()?;
+            for record in &records {
+                store.put(record)?;
+            }
+            log::info!("Imported {} highscore(s) from {:?}", records.len(), import);
+        }
+        Ok(store)
+    }
+}
+
+#[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+impl HighscoreStore for SledStore {
+    fn load_all(&mut self) -> anyhow::Result<Vec<HighscoreRecord>> {
+        let mut records = Vec::new();
+        for entry in self.db.iter() {
+            let (_key, value) = entry?;
+            records.push(serde_json::from_slice(&value)?);
+        }
+        Ok(records)
+    }
+    fn put(&mut self, record: &HighscoreRecord) -> anyhow::Result<()> {
+        self.db
+            .insert(record.name.as_bytes(), serde_json::to_vec(record)?)?;
+        self.db.flush()?;
+        Ok(())
+    }
+    fn remove(&mut self, name: &str) -> anyhow::Result<bool> {
+        let removed = self.db.remove(name.as_bytes())?.is_some();
+        self.db.flush()?;
+        Ok(removed)
+    }
+    fn clear(&mut self) -> anyhow::Result<()> {
+        self.db.clear()?;
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+/// Load every record, moving the ones stored under names from before normalization
+/// and keeping the best score when names collide
+pub fn migrate_highscore_names(
+    store: &mut dyn HighscoreStore,
+) -> anyhow::Result<Vec<HighscoreRecord>> {
+    let mut best = std::collections::BTreeMap::<String, HighscoreRecord>::new();
+    let mut moved = HashSet::new();
+    for mut record in store.load_all()? {
+        let name = normalize_name(&record.name);
+        if name != record.name {
+            store.remove(&record.name)?;
+            record.name = name.clone();
+            moved.insert(name.clone());
+        }
+        match best.get(&name) {
+            Some(other) if other.score >= record.score => {}
+            _ => {
+                best.insert(name, record);
+            }
+        }
+    }
+    for name in &moved {
+        store.put(&best[name])?;
+    }
+    if !moved.is_empty() {
+        log::info!("Moved highscores of {} renamed player(s)", moved.len());
+    }
+    Ok(best.into_values().collect())
+}
+
+pub fn open_highscores() -> anyhow::Result<Box<dyn HighscoreStore>> {
+    let backend = std::env::var("HIGHSCORES_BACKEND").unwrap_or_else(|_| "json".to_owned());
+    match backend.as_str() {
+        "json" => {
+            let path = state_path("HIGHSCORES", "highscores.json");
+            Ok(Box::new(JsonStore::open(path)?))
+        }
+        #[cfg(all(feature = "sled", not(target_arch = "wasm32")))]
+        "sled" => {
+            let path = state_path("HIGHSCORES", "highscores.db");
+            Ok(Box::new(SledStore::open(
+                &path,
+                "highscores.json".as_ref(),
+            )?))
+        }
+        _ => anyhow::bail!("Unsupported highscores backend {:?}", backend),
+    }
+}
@@ -1,20 +1,92 @@
 use super::*;
+use anyhow::Context as _;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod admin;
+mod boards;
+mod claims;
+mod data;
+mod export;
+mod highscores;
+mod notify;
+mod presets;
+mod results;
+mod seasons;
+mod snapshot;
+mod stats;
+#[cfg(not(target_arch = "wasm32"))]
+mod status;
+mod tournament;
 mod track;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use admin::*;
+pub use boards::*;
+use claims::*;
+use data::*;
+pub use export::*;
+pub use highscores::*;
+pub use notify::*;
+use presets::*;
+pub use results::*;
+pub use seasons::*;
+use snapshot::*;
+pub use stats::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use status::*;
+pub use tournament::*;
 use track::*;
 
+const CONFIG_CHECK_INTERVAL: f32 = 1.0;
+
 pub struct Model {
     pub shared: SharedModel,
     pub track_gen: TrackGen,
+    pub obstacles: Vec<ObstacleConfig>,
+    pub snapshot_timer: f32,
+    pub clients: HashMap<Id, Handshake>,
+    /// Last name refused to each client, so the refusal is only sent once
+    pub refused_names: HashMap<Id, String>,
+    pub bans: HashSet<String>,
+    pub address_bans: HashSet<std::net::IpAddr>,
+    pub claims: Claims,
+    pub max_players: Option<usize>,
+    pub preset: Option<String>,
+    pub pending_config: Option<(Config, Option<String>)>,
+    pub config_modified: Option<std::time::SystemTime>,
+    pub config_check_timer: f32,
+    pub race: Option<Race>,
+    pub next_race_id: u64,
+    pub notifier: Notifier,
+    pub highscore_store: Box<dyn HighscoreStore>,
+    pub highscores: HashMap<String, i32>,
+    pub boards: Boards,
+    pub stats: Stats,
+    pub seasons: Seasons,
 }
 
 impl Model {
-    pub fn new() -> Self {
-        discord::send_activity("Server started :green_circle:");
-        let config: Config = Self::read_config();
-        let track_gen = TrackGen::new(&config.track);
-        Self {
+    pub fn new(
+        max_players: Option<usize>,
+        preset: Option<String>,
+        rollover: Rollover,
+        season_ends: Vec<u64>,
+    ) -> anyhow::Result<Self> {
+        let config: Config = Self::read_config(preset.as_deref())?;
+        let obstacles = load_obstacles()?;
+        let tournament = load_tournament()?;
+        let seed = match &tournament {
+            Some(tournament) if !tournament.is_finished() => tournament.seed,
+            _ => thread_rng().gen(),
+        };
+        let track_gen = TrackGen::new(&config.track, &obstacles, seed);
+        let mut highscore_store = open_highscores()?;
+        let highscores = migrate_highscore_names(highscore_store.as_mut())?
+            .into_iter()
+            .map(|record| (record.name, record.score))
+            .collect();
+        let boards = Boards::load(rollover)?;
+        let mut model = Self {
             shared: SharedModel {
                 reset_timer: 0.0,
                 tick: 0,
@@ -24,53 +96,403 @@ impl Model {
                 players: default(),
                 track: track_gen.init(),
                 config,
+                preset: preset.clone(),
                 winner: None,
-                highscores: {
-                    let path = std::path::Path::new("highscores.json");
-                    if path.is_file() {
-                        serde_json::from_reader(std::fs::File::open(path).unwrap()).unwrap()
-                    } else {
-                        default()
-                    }
-                },
                 scores: default(),
+                ratings: default(),
+                tournament,
             },
             track_gen,
+            obstacles,
+            snapshot_timer: SNAPSHOT_INTERVAL,
+            clients: default(),
+            refused_names: default(),
+            bans: Self::load_bans()?,
+            address_bans: Self::load_address_bans()?,
+            claims: Claims::load()?,
+            max_players,
+            preset,
+            pending_config: None,
+            config_modified: Self::config_modified(),
+            config_check_timer: CONFIG_CHECK_INTERVAL,
+            race: None,
+            next_race_id: RaceRecord::next_id(),
+            notifier: Notifier::from_env(),
+            highscore_store,
+            highscores,
+            boards,
+            stats: Stats::load()?,
+            seasons: Seasons::load(season_ends)?,
+        };
+        if let Some(snapshot) = Snapshot::load() {
+            model.restore(snapshot);
+        }
+        model.notifier.notify(Activity::ServerStarted);
+        Ok(model)
+    }
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            shared: SharedModel {
+                players: default(),
+                track: self.track_gen.init(),
+                ..self.shared.clone()
+            },
+            track_seed: self.track_gen.seed(),
+            race: self.race.clone(),
+        }
+    }
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        log::info!("Restoring from snapshot at tick {}", snapshot.shared.tick);
+        let config = self.shared.config.clone();
+        let preset = self.shared.preset.clone();
+        // The tournament file is newer than any snapshot
+        let tournament = self.shared.tournament.clone();
+        self.track_gen = TrackGen::new(&config.track, &self.obstacles, snapshot.track_seed);
+        self.shared = SharedModel {
+            config,
+            preset,
+            tournament,
+            players: default(),
+            track: self.track_gen.init(),
+            ..snapshot.shared
+        };
+        self.race = snapshot.race;
+    }
+    fn check_name(&self, name: &str) -> Result<(), String> {
+        if self.bans.contains(name) {
+            return Err("You are banned from this server".to_owned());
+        }
+        Ok(())
+    }
+    pub fn ban(&mut self, name: &str) {
+        self.bans.insert(normalize_name(name));
+        self.save_bans();
+    }
+    pub fn unban(&mut self, name: &str) -> bool {
+        let removed = self.bans.remove(&normalize_name(name));
+        if removed {
+            self.save_bans();
+        }
+        removed
+    }
+    fn bans_path() -> std::path::PathBuf {
+        state_path("BANS", "bans.json")
+    }
+    fn load_bans() -> anyhow::Result<HashSet<String>> {
+        let path = &Self::bans_path();
+        let bans: HashSet<String> = read_json_or_default(path)?;
+        let normalized: HashSet<String> = bans.iter().map(|name| normalize_name(name)).collect();
+        if normalized != bans {
+            write_json_atomic(path, &normalized)?;
+        }
+        Ok(normalized)
+    }
+    fn save_bans(&self) {
+        if let Err(e) = write_json_atomic(&Self::bans_path(), &self.bans) {
+            log::error!("Failed to save bans: {:#}", e);
+        }
+    }
+    pub fn ban_address(&mut self, addr: std::net::IpAddr) {
+        if self.address_bans.insert(addr) {
+            self.save_address_bans();
+        }
+    }
+    pub fn unban_address(&mut self, addr: &std::net::IpAddr) -> bool {
+        let removed = self.address_bans.remove(addr);
+        if removed {
+            self.save_address_bans();
+        }
+        removed
+    }
+    fn address_bans_path() -> std::path::PathBuf {
+        state_path("ADDRESS_BANS", "address_bans.json")
+    }
+    fn load_address_bans() -> anyhow::Result<HashSet<std::net::IpAddr>> {
+        read_json_or_default(&Self::address_bans_path())
+    }
+    fn save_address_bans(&self) {
+        if let Err(e) = write_json_atomic(&Self::address_bans_path(), &self.address_bans) {
+            log::error!("Failed to save address bans: {:#}", e);
+        }
+    }
+    pub fn start_race(&mut self) {
+        if self.shared.avalanche_position.is_none() {
+            for player in &mut self.shared.players {
+                player.position.y = 0.0;
+            }
+            self.shared.scores.clear();
+            self.shared.avalanche_position = Some(self.shared.config.avalanche.start);
+            let heat = self.take_armed_heat();
+            self.race = Some(Race {
+                id: self.next_race_id,
+                seed: self.track_gen.seed(),
+                preset: self.shared.preset.clone(),
+                start_time: unix_time(),
+                crashes: default(),
+                participants: default(),
+                heat,
+            });
+            self.next_race_id += 1;
+        }
+    }
+    pub fn reset_race(&mut self) {
+        self.race = None;
+        self.shared.avalanche_position = None;
+        self.shared.avalanche_speed = self.shared.config.avalanche.min_speed;
+        self.shared.scores.clear();
+        self.new_track();
+    }
+    fn new_track(&mut self) {
+        self.track_gen = TrackGen::new(
+            &self.shared.config.track,
+            &self.obstacles,
+            self.next_track_seed(),
+        );
+        self.shared.track = self.track_gen.init();
+    }
+    pub fn reload_config(&mut self) -> anyhow::Result<()> {
+        let config = Self::read_config(self.preset.as_deref())?;
+        self.pending_config = Some((config, self.preset.clone()));
+        log::info!("Config reloaded, it will be applied before the next race");
+        Ok(())
+    }
+    pub fn set_preset(&mut self, preset: Option<String>) -> anyhow::Result<()> {
+        let config = Self::read_config(preset.as_deref())?;
+        self.preset = preset.clone();
+        self.pending_config = Some((config, preset));
+        Ok(())
+    }
+    fn apply_pending_config(&mut self, events: &mut Vec<Event>) {
+        if self.shared.avalanche_position.is_some() {
+            return;
+        }
+        if let Some((config, preset)) = self.pending_config.take() {
+            self.shared.config = config;
+            self.shared.preset = preset;
+            self.reset_race();
+            events.push(Event::Announcement("Config updated".to_owned()));
+        }
+    }
+    fn check_config_file(&mut self) {
+        let modified = Self::config_modified();
+        if modified != self.config_modified {
+            self.config_modified = modified;
+            if let Err(e) = self.reload_config() {
+                log::error!("Failed to reload config: {:#}", e);
+            }
+        }
+    }
+    fn config_modified() -> Option<std::time::SystemTime> {
+        [Self::config_path(), presets_path()]
+            .into_iter()
+            .filter_map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .max()
+    }
+    pub fn remove_highscore(&mut self, name: &str) -> anyhow::Result<bool> {
+        let name = &normalize_name(name);
+        let removed = self.highscore_store.remove(name)?;
+        self.highscores.remove(name);
+        self.boards.remove(name);
+        Ok(removed)
+    }
+    fn remove_player(&mut self, player_id: Id) {
+        if let Some(player) = self.shared.players.remove(&player_id) {
+            self.notifier
+                .notify(Activity::PlayerLeft { name: player.name });
         }
     }
-    pub fn read_config() -> Config {
-        match std::env::var("CONFIG") {
-            Ok(path) => serde_json::from_reader(std::fs::File::open(path).unwrap()).unwrap(),
-            Err(_) => serde_json::from_reader(
-                std::fs::File::open(assets_path().join("config.json")).unwrap(),
-            )
-            .unwrap(),
+    pub fn ranked(&self, board: Board) -> Vec<LeaderboardEntry> {
+        let mut ranked = match board {
+            Board::AllTime => rank_scores(&self.highscores),
+            Board::Rating => rank_scores(&self.stats.ratings()),
+            _ => rank_scores(self.boards.scores(board).into_iter().flatten()),
+        };
+        for entry in &mut ranked {
+            entry.rating = self.stats.rating(&entry.name);
+        }
+        ranked
+    }
+    /// Archive the season once its end date has passed and start everyone over
+    fn check_season(&mut self, events: &mut Vec<Event>) {
+        // Every end date that passed while the server was down finishes its own season
+        while let Some(end) = self.seasons.due() {
+            if !self.finish_season(end, events) {
+                return;
+            }
+        }
+    }
+    fn finish_season(&mut self, end: u64, events: &mut Vec<Event>) -> bool {
+        let number = self.seasons.current.number;
+        let archive = SeasonArchive {
+            number,
+            start: self.seasons.current.start,
+            end,
+            highscores: self.ranked(Board::AllTime),
+            ratings: self.ranked(Board::Rating),
+            stats: self.stats.players.clone(),
+        };
+        if let Err(e) = self.seasons.finish(&archive) {
+            log::error!("Failed to archive season {}: {:#}", number, e);
+            return false;
+        }
+        log::info!("Season {} is over, starting season {}", number, number + 1);
+        match self.highscore_store.clear() {
+            Ok(()) => self.highscores.clear(),
+            Err(e) => log::error!("Failed to clear highscores: {:#}", e),
+        }
+        self.boards.clear();
+        self.stats.clear();
+        self.update_ratings();
+        events.push(Event::Announcement(format!("season {} is over", number)));
+        true
+    }
+    fn update_ratings(&mut self) {
+        self.shared.ratings = self
+            .clients
+            .values()
+            .filter_map(|client| Some((client.name.clone(), self.stats.rating(&client.name)?)))
+            .collect();
+    }
+    fn sorted_scores(&self) -> Vec<(String, i32)> {
+        let mut scores: Vec<(String, i32)> = self
+            .shared
+            .scores
+            .iter()
+            .map(|(a, b)| (a.clone(), *b))
+            .collect();
+        scores.sort_by_key(|(_name, score)| -score);
+        scores
+    }
+    fn save_highscore(&mut self, name: &str, score: i32) {
+        let record = HighscoreRecord {
+            name: name.to_owned(),
+            score,
+            time: unix_time(),
+            seed: self
+                .race
+                .as_ref()
+                .map_or(self.track_gen.seed(), |race| race.seed),
+            preset: self.shared.preset.clone(),
+        };
+        if let Err(e) = self.highscore_store.put(&record) {
+            log::error!("Failed to save highscore of {:?}: {:#}", name, e);
+        }
+    }
+    pub fn config_path() -> std::path::PathBuf {
+        state_path("CONFIG", data_dir().join("config.json"))
+    }
+    pub fn read_config(preset: Option<&str>) -> anyhow::Result<Config> {
+        let path = Self::config_path();
+        let mut json: serde_json::Value = read_json(&path)?;
+        let mut what = format!("config {:?}", path);
+        if let Some(name) = preset {
+            let mut presets = load_presets()?;
+            let overlay = presets.remove(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown preset {:?}, available: {:?}",
+                    name,
+                    presets.keys().collect::<Vec<_>>(),
+                )
+            })?;
+            apply_preset(&mut json, overlay);
+            what = format!("{} with preset {:?}", what, name);
+        }
+        let config: Config =
+            serde_json::from_value(json).with_context(|| format!("Failed to parse {}", what))?;
+        validate(&config, &what)?;
+        Ok(config)
+    }
+    pub fn check_config() -> Vec<anyhow::Error> {
+        let mut errors = Vec::new();
+        if let Err(e) = Self::read_config(None) {
+            errors.push(e);
+        }
+        match load_presets() {
+            Ok(presets) => {
+                for name in presets.keys() {
+                    if let Err(e) = Self::read_config(Some(name)) {
+                        errors.push(e);
+                    }
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+        if let Err(e) = load_obstacles() {
+            errors.push(e);
         }
+        errors
     }
 }
 
-impl simple_net::Model for Model {
+impl simple_net::Protocol for Model {
     type SharedState = SharedModel;
-    fn shared_state(&self) -> &Self::SharedState {
-        &self.shared
-    }
     type PlayerId = Id;
     type Message = Message;
     type Event = Event;
-    const TICKS_PER_SECOND: f32 = TICKS_PER_SECOND;
-    fn new_player(&mut self, _events: &mut Vec<Event>) -> Self::PlayerId {
+    type Handshake = Handshake;
+    fn event_recipient(event: &Event) -> Option<&Id> {
+        event.recipient()
+    }
+}
+
+impl simple_net::Model for Model {
+    fn shared_state(&self) -> &Self::SharedState {
+        &self.shared
+    }
+    fn new_player(
+        &mut self,
+        events: &mut Vec<Event>,
+        handshake: Handshake,
+    ) -> Result<(Id, Handshake), String> {
+        if let Some(room) = &handshake.room {
+            return Err(format!("Room {:?} does not exist on this server", room));
+        }
+        let mut handshake = handshake;
+        handshake.name = normalize_name(&handshake.name);
+        self.check_name(&handshake.name)?;
+        // Same as on UpdatePlayer, someone else's secret skin just falls back to the plain one
+        if let Some(skin) = &mut handshake.skin {
+            if skin.secret.as_ref() != Some(&handshake.name) {
+                skin.secret = None;
+            }
+        }
+        if handshake.role == Role::Player {
+            if let Some(max_players) = self.max_players {
+                let players = self
+                    .clients
+                    .values()
+                    .filter(|client| client.role == Role::Player)
+                    .count();
+                if players >= max_players {
+                    log::info!("Server is full, {:?} joins as a spectator", handshake.name);
+                    handshake.role = Role::Spectator;
+                }
+            }
+        }
         let player_id = self.shared.next_id;
         self.shared.next_id += 1;
-        player_id
+        let token = handshake.token.get_or_insert_with(new_token);
+        let refused = if let Err(reason) = validate_name(&handshake.name) {
+            Some(reason)
+        } else if !self.claims.allows(&handshake.name, Some(token.as_str())) {
+            Some(format!("name {} is taken", handshake.name))
+        } else {
+            None
+        };
+        if let Some(reason) = refused {
+            log::info!("Refused name {:?}: {}", handshake.name, reason);
+            self.refused_names
+                .insert(player_id, mem::take(&mut handshake.name));
+            events.push(Event::NameRefused { player_id, reason });
+        }
+        self.clients.insert(player_id, handshake.clone());
+        Ok((player_id, handshake))
     }
 
     fn drop_player(&mut self, _events: &mut Vec<Event>, player_id: &Self::PlayerId) {
-        if let Some(player) = self.shared.players.remove(&player_id) {
-            discord::send_activity(&format!(
-                "{} left the server :woman_tipping_hand:",
-                player.name
-            ));
-        }
+        self.remove_player(*player_id);
+        self.clients.remove(player_id);
+        self.refused_names.remove(player_id);
     }
 
     fn handle_message(
@@ -82,71 +504,139 @@ impl simple_net::Model for Model {
         let player_id = *player_id;
         match message {
             Message::Disconnect => {
-                self.drop_player(events, &player_id);
+                self.remove_player(player_id);
             }
-            Message::UpdatePlayer(player) => {
+            Message::UpdatePlayer(mut player) => {
                 if player.id != player_id {
                     return;
                 }
+                let client = match self.clients.get_mut(&player_id) {
+                    Some(client) => client,
+                    None => return,
+                };
+                if client.role == Role::Spectator {
+                    return;
+                }
+                player.name = normalize_name(&player.name);
+                if player.name != client.name {
+                    let refused = if self.bans.contains(&player.name) {
+                        Some(format!("name {} is banned", player.name))
+                    } else if let Err(reason) = validate_name(&player.name) {
+                        Some(reason)
+                    } else if !self.claims.allows(&player.name, client.token.as_deref()) {
+                        Some(format!("name {} is taken", player.name))
+                    } else {
+                        None
+                    };
+                    match refused {
+                        Some(reason) => {
+                            if self.refused_names.get(&player_id) != Some(&player.name) {
+                                events.push(Event::NameRefused { player_id, reason });
+                                self.refused_names.insert(player_id, player.name.clone());
+                            }
+                            player.name = client.name.clone();
+                        }
+                        None => {
+                            client.name = player.name.clone();
+                            self.refused_names.remove(&player_id);
+                        }
+                    }
+                }
+                if player.config.secret.as_ref() != Some(&player.name) {
+                    player.config.secret = None;
+                }
+                client.skin = Some(player.config.clone());
+                // A race only counts as started for players who actually set off
+                let started_riding = matches!(player.state, PlayerState::Ride { .. })
+                    && !matches!(
+                        self.shared.players.get(&player_id).map(|old| &old.state),
+                        Some(PlayerState::Ride { .. })
+                    );
+                if let Some(race) = self.race.as_mut().filter(|_| started_riding) {
+                    if race.participants.insert(player.name.clone()) {
+                        self.stats.race_started(&player.name);
+                    }
+                }
+                if let (Some(old), PlayerState::Crash { cause, .. }) =
+                    (self.shared.players.get(&player_id), &player.state)
+                {
+                    if !matches!(old.state, PlayerState::Crash { .. }) {
+                        self.stats.crashed(&player.name, *cause);
+                        if let Some(race) = &mut self.race {
+                            *race.crashes.entry(player.name.clone()).or_default() += 1;
+                        }
+                    }
+                }
                 if self.shared.players.get(&player_id).is_none() {
-                    discord::send_activity(&format!(
-                        "{} just joined the server :man_raising_hand:",
-                        player.name
-                    ));
+                    self.notifier.notify(Activity::PlayerJoined {
+                        name: player.name.clone(),
+                    });
                 }
                 self.shared.players.insert(player);
             }
             Message::Score(score) => {
-                if let Some(player) = self.shared.players.get(&player_id) {
-                    let last_score = self.shared.scores.get(&player.name).copied().unwrap_or(0);
-                    if score > last_score {
-                        self.shared.scores.insert(player.name.clone(), score);
-                    }
+                let name = match self.shared.players.get(&player_id) {
+                    Some(player) => player.name.clone(),
+                    None => return,
+                };
+                // A name becomes someone's once they score with it
+                if let Some(token) = self
+                    .clients
+                    .get(&player_id)
+                    .and_then(|client| client.token.as_ref())
+                {
+                    self.claims.claim(&name, token);
+                }
+                let last_score = self.shared.scores.get(&name).copied().unwrap_or(0);
+                if score > last_score {
+                    self.shared.scores.insert(name.clone(), score);
+                }
 
-                    let current_highest_score =
-                        self.shared.highscores.values().max().copied().unwrap_or(0);
+                let current_highest_score = self.highscores.values().max().copied().unwrap_or(0);
 
-                    if score
-                        > self
-                            .shared
-                            .highscores
-                            .get(&player.name)
-                            .copied()
-                            .unwrap_or(0)
-                    {
-                        discord::send_activity(&format!(
-                            "New personal best of {} by {} :thumbsup:",
-                            score, player.name
-                        ));
-                        self.shared.highscores.insert(player.name.clone(), score);
-                        serde_json::to_writer_pretty(
-                            std::fs::File::create("highscores.json").unwrap(),
-                            &self.shared.highscores,
-                        )
-                        .unwrap();
-                    }
-                    if score > current_highest_score {
-                        discord::send_activity(&format!(
-                            "New highscore of {} by {} <:extremeBoom:963122644373368832>",
-                            score, player.name,
-                        ));
-                    }
+                if score > self.highscores.get(&name).copied().unwrap_or(0) {
+                    self.notifier.notify(Activity::PersonalBest {
+                        name: name.clone(),
+                        score,
+                    });
+                    self.highscores.insert(name.clone(), score);
+                    self.save_highscore(&name, score);
+                }
+                self.boards.submit(&name, score);
+                if score > current_highest_score {
+                    self.notifier.notify(Activity::Record { name, score });
                 }
             }
             Message::StartTheRace => {
-                if self.shared.avalanche_position.is_none() {
-                    for player in &mut self.shared.players {
-                        player.position.y = 0.0;
-                    }
-                    self.shared.scores.clear();
-                    self.shared.avalanche_position = Some(self.shared.config.avalanche.start);
-                }
+                self.start_race();
+            }
+            Message::RequestLeaderboard(request) => {
+                let name = match self.clients.get(&player_id) {
+                    Some(client) => client.name.clone(),
+                    None => return,
+                };
+                let page = LeaderboardPage::new(request, &self.ranked(request.board), &name);
+                events.push(Event::Leaderboard { player_id, page });
+            }
+            Message::RequestProfile(name) => {
+                let profile = Profile {
+                    stats: self.stats.players.get(&name).cloned().unwrap_or_default(),
+                    highscore: self
+                        .ranked(Board::AllTime)
+                        .into_iter()
+                        .find(|entry| entry.name == name),
+                    name,
+                };
+                events.push(Event::Profile { player_id, profile });
+            }
+            Message::RequestSeason(number) => {
+                let season = self.seasons.summary(number);
+                events.push(Event::Season { player_id, season });
             }
         }
     }
 
-    fn tick(&mut self, _events: &mut Vec<Event>) {
-        let delta_time = 1.0 / TICKS_PER_SECOND;
+    fn tick(&mut self, events: &mut Vec<Event>, delta_time: f32) {
         self.shared.tick += 1;
         if let Some(position) = &mut self.shared.avalanche_position {
             let avalanche_acceleration = (self.shared.config.avalanche.max_speed
@@ -172,30 +662,27 @@ impl simple_net::Model for Model {
                     if self.shared.reset_timer < 0.0 {
                         self.shared.avalanche_position = None;
                         self.shared.avalanche_speed = self.shared.config.avalanche.min_speed;
-                        self.track_gen = TrackGen::new(&self.shared.config.track);
-                        self.shared.track = self.track_gen.init();
+                        let race = self.race.take();
                         if !self.shared.scores.is_empty() {
-                            let mut scores: Vec<(String, i32)> = self
-                                .shared
-                                .scores
-                                .iter()
-                                .map(|(a, b)| (a.clone(), *b))
-                                .collect();
-                            scores.sort_by_key(|(_name, score)| -score);
-                            self.shared.winner = Some(scores[0].clone());
-                            let mut text = "Race results:".to_owned();
-                            for (index, (name, score)) in scores.iter().enumerate() {
-                                text.push('\n');
-                                text.push_str(&(index + 1).to_string());
-                                text.push_str(". ");
-                                text.push_str(name);
-                                text.push_str(" - ");
-                                text.push_str(&score.to_string());
+                            let scores = self.sorted_scores();
+                            if let Some(race) = race {
+                                let heat = race.heat;
+                                let record = race.finish(&scores);
+                                if let Err(e) = record.append() {
+                                    log::error!("Failed to save race results: {:#}", e);
+                                }
+                                self.stats.race_finished(&record);
+                                self.update_ratings();
+                                if heat {
+                                    self.record_heat(&record, events);
+                                }
                             }
-                            text.push_str("\n<:extremeBoom:963122644373368832>");
-                            discord::send_activity(&text);
+                            self.shared.winner = Some(scores[0].clone());
+                            self.notifier.notify(Activity::race_results(false, &scores));
                             self.shared.scores.clear();
                         }
+                        // After the results so the last heat of a tournament frees the seed
+                        self.new_track();
                     }
                 } else {
                     self.shared.reset_timer = 0.0;
@@ -213,5 +700,38 @@ impl simple_net::Model for Model {
             round(pos + OFF),
             round(pos - self.shared.config.avalanche.start - OFF),
         );
+        self.config_check_timer -= delta_time;
+        if self.config_check_timer < 0.0 {
+            self.config_check_timer = CONFIG_CHECK_INTERVAL;
+            self.check_config_file();
+            self.boards.roll_over();
+            self.check_season(events);
+            self.update_ratings();
+        }
+        self.apply_pending_config(events);
+        self.snapshot_timer -= delta_time;
+        if self.snapshot_timer < 0.0 {
+            self.snapshot_timer = SNAPSHOT_INTERVAL;
+            if let Err(e) = self.snapshot().save() {
+                log::error!("Failed to save snapshot: {}", e);
+            }
+            self.stats.save();
+        }
+    }
+
+    fn allow_address(&self, addr: &std::net::IpAddr) -> bool {
+        !self.address_bans.contains(addr)
+    }
+    fn shutdown(&mut self) {
+        if !self.shared.scores.is_empty() {
+            self.notifier
+                .notify(Activity::race_results(true, &self.sorted_scores()));
+        }
+        if let Err(e) = self.snapshot().save() {
+            log::error!("Failed to save snapshot: {}", e);
+        }
+        self.stats.save();
+        self.notifier.notify(Activity::ServerStopped);
+        self.notifier.flush();
     }
 }
@@ -0,0 +1,524 @@
+use super::*;
+
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+const RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(2);
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+const DISCORD_MESSAGE_LIMIT: usize = 2000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Standing {
+    pub place: usize,
+    pub name: String,
+    pub score: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Activity {
+    ServerStarted,
+    ServerStopped,
+    PlayerJoined {
+        name: String,
+    },
+    PlayerLeft {
+        name: String,
+    },
+    PersonalBest {
+        name: String,
+        score: i32,
+    },
+    Record {
+        name: String,
+        score: i32,
+    },
+    RaceResults {
+        interrupted: bool,
+        standings: Vec<Standing>,
+    },
+}
+
+impl Activity {
+    pub fn race_results(interrupted: bool, scores: &[(String, i32)]) -> Self {
+        Self::RaceResults {
+            interrupted,
+            standings: scores
+                .iter()
+                .enumerate()
+                .map(|(index, (name, score))| Standing {
+                    place: index + 1,
+                    name: name.clone(),
+                    score: *score,
+                })
+                .collect(),
+        }
+    }
+    pub fn text(&self) -> String {
+        match self {
+            Self::ServerStarted => "Server started".to_owned(),
+            Self::ServerStopped => "Server stopped".to_owned(),
+            Self::PlayerJoined { name } => format!("{} just joined the server", name),
+            Self::PlayerLeft { name } => format!("{} left the server", name),
+            Self::PersonalBest { name, score } => {
+                format!("New personal best of {} by {}", score, name)
+            }
+            Self::Record { name, score } => format!("New highscore of {} by {}", score, name),
+            Self::RaceResults {
+                interrupted,
+                standings,
+            } => {
+                let mut text = if *interrupted {
+                    "Race interrupted by server shutdown, standings:"
+                } else {
+                    "Race results:"
+                }
+                .to_owned();
+                for standing in standings {
+                    text.push_str(&format!(
+                        "\n{}. {} - {}",
+                        standing.place, standing.name, standing.score
+                    ));
+                }
+                text
+            }
+        }
+    }
+    #[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+    fn discord_text(&self) -> String {
+        let emoji = match self {
+            Self::ServerStarted => ":green_circle:",
+            Self::ServerStopped => ":red_circle:",
+            Self::PlayerJoined { .. } => ":man_raising_hand:",
+            Self::PlayerLeft { .. } => ":woman_tipping_hand:",
+            Self::PersonalBest { .. } => ":thumbsup:",
+            Self::Record { .. } | Self::RaceResults { .. } => "<:extremeBoom:963122644373368832>",
+        };
+        let separator = match self {
+            Self::RaceResults { .. } => '\n',
+            _ => ' ',
+        };
+        format!("{}{}{}", self.text(), separator, emoji)
+    }
+}
+
+pub trait Sink: Send {
+    fn name(&self) -> String;
+    /// Deliver the batch, advancing `sent` past every activity that got through
+    /// so a retry only sends the rest
+    fn send(&mut self, batch: &[Activity], sent: &mut usize) -> anyhow::Result<()>;
+}
+
+#[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+pub struct DiscordWebhook {
+    client: reqwest::blocking::Client,
+    url: String,
+}
+
+#[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+impl DiscordWebhook {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::blocking::Client::new(),
+            url,
+        }
+    }
+}
+
+#[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+impl Sink for DiscordWebhook {
+    fn name(&self) -> String {
+        "Discord webhook".to_owned()
+    }
+    fn send(&mut self, batch: &[Activity], sent: &mut usize) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct Data<'a> {
+            content: &'a str,
+        }
+        // Discord rejects longer messages, so a big batch is split between activities,
+        // each message remembering how many activities it completes
+        let mut messages = vec![(String::new(), 0)];
+        for activity in batch {
+            let pieces = split_message(&activity.discord_text(), DISCORD_MESSAGE_LIMIT);
+            let last = pieces.len() - 1;
+            for (index, text) in pieces.into_iter().enumerate() {
+                let (message, _) = messages.last().unwrap();
+                if !message.is_empty() && message.len() + 1 + text.len() > DISCORD_MESSAGE_LIMIT {
+                    messages.push((String::new(), 0));
+                }
+                let (message, count) = messages.last_mut().unwrap();
+                if !message.is_empty() {
+                    message.push('\n');
+                }
+                message.push_str(&text);
+                if index == last {
+                    *count += 1;
+                }
+            }
+        }
+        for (message, count) in &messages {
+            self.client
+                .post(&self.url)
+                .json(&Data { content: message })
+                .send()?
+                .error_for_status()?;
+            *sent += count;
+        }
+        Ok(())
+    }
+}
+
+/// Split text into pieces of at most `limit` bytes, between lines where possible
+#[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut pieces = vec![String::new()];
+    for mut line in text.split('\n') {
+        loop {
+            let piece = pieces.last_mut().unwrap();
+            if piece.is_empty() && line.len() <= limit {
+                piece.push_str(line);
+                break;
+            }
+            if !piece.is_empty() && piece.len() + 1 + line.len() <= limit {
+                piece.push('\n');
+                piece.push_str(line);
+                break;
+            }
+            if !piece.is_empty() {
+                pieces.push(String::new());
+                continue;
+            }
+            // A single line too long for a message of its own is cut
+            let mut end = limit;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            piece.push_str(&line[..end]);
+            line = &line[end..];
+            pieces.push(String::new());
+        }
+    }
+    pieces.retain(|piece| !piece.is_empty());
+    if pieces.is_empty() {
+        pieces.push(String::new());
+    }
+    pieces
+}
+
+#[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+pub struct JsonWebhook {
+    client: reqwest::blocking::Client,
+    url: String,
+}
+
+#[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+impl JsonWebhook {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::blocking::Client::new(),
+            url,
+        }
+    }
+}
+
+#[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+impl Sink for JsonWebhook {
+    fn name(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+    fn send(&mut self, batch: &[Activity], sent: &mut usize) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(batch)
+            .send()?
+            .error_for_status()?;
+        *sent += batch.len();
+        Ok(())
+    }
+}
+
+pub struct LogFile {
+    path: std::path::PathBuf,
+}
+
+impl LogFile {
+    pub fn new(path: std::path::PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Sink for LogFile {
+    fn name(&self) -> String {
+        format!("activity log {:?}", self.path)
+    }
+    fn send(&mut self, batch: &[Activity], sent: &mut usize) -> anyhow::Result<()> {
+        #[derive(Serialize)]
+        struct Line<'a> {
+            time: u64,
+            #[serde(flatten)]
+            activity: &'a Activity,
+        }
+        let time = unix_time();
+        let mut text = String::new();
+        for activity in batch {
+            text.push_str(&serde_json::to_string(&Line { time, activity })?);
+            text.push('\n');
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        std::io::Write::write_all(&mut file, text.as_bytes())?;
+        *sent += batch.len();
+        Ok(())
+    }
+}
+
+fn sinks_from_env() -> Vec<Box<dyn Sink>> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    #[cfg(all(feature = "discord", not(target_arch = "wasm32")))]
+    {
+        if let Ok(url) = std::env::var("DISCORD_ACTIVITY_WEBHOOK") {
+            sinks.push(Box::new(DiscordWebhook::new(url)));
+        }
+        if let Ok(url) = std::env::var("ACTIVITY_WEBHOOK") {
+            sinks.push(Box::new(JsonWebhook::new(url)));
+        }
+    }
+    #[cfg(not(all(feature = "discord", not(target_arch = "wasm32"))))]
+    for var in ["DISCORD_ACTIVITY_WEBHOOK", "ACTIVITY_WEBHOOK"] {
+        if std::env::var(var).is_ok() {
+            log::warn!("{} is set but this build has no webhook support", var);
+        }
+    }
+    if let Ok(path) = std::env::var("ACTIVITY_LOG") {
+        sinks.push(Box::new(LogFile::new(path.into())));
+    }
+    sinks
+}
+
+enum Command {
+    Send(Activity),
+    Flush(mpsc::Sender<()>),
+}
+
+pub struct Notifier {
+    sender: Option<mpsc::Sender<Command>>,
+}
+
+impl Notifier {
+    pub fn from_env() -> Self {
+        Self::new(sinks_from_env())
+    }
+    pub fn new(sinks: Vec<Box<dyn Sink>>) -> Self {
+        if sinks.is_empty() {
+            return Self { sender: None };
+        }
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || run_worker(sinks, receiver));
+        Self {
+            sender: Some(sender),
+        }
+    }
+    pub fn notify(&self, activity: Activity) {
+        log::info!("{}", activity.text());
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Command::Send(activity));
+        }
+    }
+    /// Wait until everything queued so far is delivered or dropped
+    pub fn flush(&self) {
+        if let Some(sender) = &self.sender {
+            let (ack, done) = mpsc::channel();
+            if sender.send(Command::Flush(ack)).is_ok() && done.recv_timeout(FLUSH_TIMEOUT).is_err()
+            {
+                log::warn!("Timed out delivering notifications");
+            }
+        }
+    }
+}
+
+fn run_worker(mut sinks: Vec<Box<dyn Sink>>, receiver: mpsc::Receiver<Command>) {
+    let mut next_delivery = Instant::now();
+    while let Ok(command) = receiver.recv() {
+        // Whatever arrives while rate limited is delivered together as one batch
+        std::thread::sleep(next_delivery.saturating_duration_since(Instant::now()));
+        let mut batch = Vec::new();
+        let mut flushes = Vec::new();
+        for command in std::iter::once(command).chain(receiver.try_iter()) {
+            match command {
+                Command::Send(activity) => batch.push(activity),
+                Command::Flush(ack) => flushes.push(ack),
+            }
+        }
+        if !batch.is_empty() {
+            for sink in &mut sinks {
+                deliver(sink.as_mut(), &batch);
+            }
+            next_delivery = Instant::now() + RATE_LIMIT_INTERVAL;
+        }
+        for ack in flushes {
+            let _ = ack.send(());
+        }
+    }
+}
+
+fn deliver(sink: &mut dyn Sink, batch: &[Activity]) {
+    let mut delay = RETRY_DELAY;
+    let mut sent = 0;
+    for attempt in 1..=MAX_ATTEMPTS {
+        match sink.send(&batch[sent..], &mut sent) {
+            Ok(()) => return,
+            Err(e) => log::warn!(
+                "Failed to notify {} (attempt {}/{}): {:#}",
+                sink.name(),
+                attempt,
+                MAX_ATTEMPTS,
+                e,
+            ),
+        }
+        if attempt < MAX_ATTEMPTS {
+            std::thread::sleep(delay);
+            delay *= 2;
+        }
+    }
+    log::error!(
+        "Dropped {} notification(s) for {}",
+        batch.len() - sent,
+        sink.name()
+    );
+}
+
+#[cfg(all(test, feature = "discord", not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    use std::io::{BufRead, Read, Write};
+
+    /// Answer one request per status with a local http server, passing on the request bodies
+    fn serve(statuses: Vec<u16>) -> (String, mpsc::Receiver<String>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = std::io::BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                sender.send(String::from_utf8(body).unwrap()).unwrap();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    fn joins(count: usize) -> Vec<Activity> {
+        (0..count)
+            .map(|index| Activity::PlayerJoined {
+                name: format!("player{}", index),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn json_webhook_posts_the_batch() {
+        let (url, bodies) = serve(vec![200]);
+        let batch = joins(2);
+        deliver(&mut JsonWebhook::new(url), &batch);
+        let body: serde_json::Value = serde_json::from_str(&bodies.recv().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([
+                { "type": "player_joined", "name": "player0" },
+                { "type": "player_joined", "name": "player1" },
+            ])
+        );
+    }
+
+    #[test]
+    fn discord_webhook_retries_only_unsent_messages() {
+        // Enough activities to need two messages, the second one fails once
+        let (url, bodies) = serve(vec![200, 500, 200]);
+        let batch = joins(50);
+        deliver(&mut DiscordWebhook::new(url), &batch);
+        let contents: Vec<String> = bodies
+            .try_iter()
+            .map(|body| {
+                let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+                body["content"].as_str().unwrap().to_owned()
+            })
+            .collect();
+        assert_eq!(contents.len(), 3);
+        assert!(contents[0].starts_with("player0 "));
+        assert_ne!(contents[0], contents[1]);
+        assert_eq!(contents[1], contents[2]);
+        assert!(contents[2].ends_with(&batch[49].discord_text()));
+        let lines: usize = contents[..2]
+            .iter()
+            .map(|content| content.lines().count())
+            .sum();
+        assert_eq!(lines, batch.len());
+    }
+
+    #[test]
+    fn long_messages_are_split() {
+        let text = format!(
+            "{}\n{}\n{}",
+            "a".repeat(1500),
+            "b".repeat(1500),
+            "c".repeat(4500)
+        );
+        let pieces = split_message(&text, DISCORD_MESSAGE_LIMIT);
+        assert!(pieces
+            .iter()
+            .all(|piece| piece.len() <= DISCORD_MESSAGE_LIMIT));
+        assert_eq!(pieces[0], "a".repeat(1500));
+        assert_eq!(pieces[1], "b".repeat(1500));
+        assert_eq!(pieces.concat(), text.replace('\n', ""));
+    }
+
+    #[test]
+    fn discord_webhook_delivers_long_race_results() {
+        let (url, bodies) = serve(vec![200; 10]);
+        let scores: Vec<(String, i32)> = (0..300)
+            .map(|index| (format!("player{}", index), 100000 - index))
+            .collect();
+        let batch = [Activity::race_results(false, &scores)];
+        deliver(&mut DiscordWebhook::new(url), &batch);
+        let contents: Vec<String> = bodies
+            .try_iter()
+            .map(|body| {
+                let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+                body["content"].as_str().unwrap().to_owned()
+            })
+            .collect();
+        assert!(contents.len() > 1);
+        assert!(contents
+            .iter()
+            .all(|content| content.len() <= DISCORD_MESSAGE_LIMIT));
+        assert_eq!(contents.join("\n"), batch[0].discord_text());
+    }
+}
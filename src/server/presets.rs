@@ -0,0 +1,22 @@
+use super::*;
+
+pub type Presets = std::collections::BTreeMap<String, serde_json::Value>;
+
+pub fn presets_path() -> std::path::PathBuf {
+    state_path("PRESETS", data_dir().join("presets.json"))
+}
+
+pub fn load_presets() -> anyhow::Result<Presets> {
+    read_json(&presets_path())
+}
+
+pub fn apply_preset(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                apply_preset(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
@@ -0,0 +1,142 @@
+use super::*;
+use anyhow::Context as _;
+
+pub fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Race {
+    pub id: u64,
+    pub seed: u64,
+    pub preset: Option<String>,
+    pub start_time: u64,
+    pub crashes: HashMap<String, usize>,
+    #[serde(default)]
+    pub participants: HashSet<String>,
+    /// Counts as the next tournament heat
+    #[serde(default)]
+    pub heat: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerResult {
+    pub name: String,
+    pub place: usize,
+    pub score: i32,
+    pub distance: f32,
+    pub crashes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RaceRecord {
+    pub id: u64,
+    pub seed: u64,
+    pub preset: Option<String>,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub results: Vec<PlayerResult>,
+}
+
+impl Race {
+    pub fn finish(self, scores: &[(String, i32)]) -> RaceRecord {
+        RaceRecord {
+            id: self.id,
+            seed: self.seed,
+            preset: self.preset,
+            start_time: self.start_time,
+            end_time: unix_time(),
+            results: scores
+                .iter()
+                .enumerate()
+                .map(|(index, (name, score))| PlayerResult {
+                    name: name.clone(),
+                    place: index + 1,
+                    score: *score,
+                    distance: *score as f32 / 100.0,
+                    crashes: self.crashes.get(name).copied().unwrap_or(0),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl RaceRecord {
+    pub fn path() -> std::path::PathBuf {
+        state_path("RESULTS", "results.jsonl")
+    }
+    pub fn append(&self) -> anyhow::Result<()> {
+        let path = Self::path();
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {:?}", path))?;
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        std::io::Write::write_all(&mut file, line.as_bytes())?;
+        Ok(())
+    }
+    pub fn load_all() -> anyhow::Result<Vec<Self>> {
+        let path = Self::path();
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let file =
+            std::fs::File::open(&path).with_context(|| format!("Failed to open {:?}", path))?;
+        let mut records = Vec::new();
+        for (index, line) in std::io::BufRead::lines(std::io::BufReader::new(file)).enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(record) => records.push(record),
+                // A crash while appending can leave a partial last line
+                Err(e) => log::warn!("Skipping {:?} line {}: {}", path, index + 1, e),
+            }
+        }
+        Ok(records)
+    }
+    pub fn next_id() -> u64 {
+        match Self::load_all() {
+            Ok(records) => records
+                .iter()
+                .map(|record| record.id + 1)
+                .max()
+                .unwrap_or(0),
+            Err(e) => {
+                log::error!("Failed to read race results: {:#}", e);
+                0
+            }
+        }
+    }
+}
+
+pub fn print_recent_races(count: usize) -> anyhow::Result<()> {
+    let records = RaceRecord::load_all()?;
+    if records.is_empty() {
+        println!("No races recorded in {:?}", RaceRecord::path());
+        return Ok(());
+    }
+    for record in records.iter().rev().take(count) {
+        println!(
+            "Race #{} started at {} lasted {}s, seed {}, preset {}",
+            record.id,
+            record.start_time,
+            record.end_time.saturating_sub(record.start_time),
+            record.seed,
+            record.preset.as_deref().unwrap_or("none"),
+        );
+        for result in &record.results {
+            println!(
+                "  {:>2}. {:<15} {:>7} ({:.1}m, {} crashes)",
+                result.place, result.name, result.score, result.distance, result.crashes,
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use super::*;
+use anyhow::Context as _;
+
+/// Number of players kept in a season summary sent to the lobby
+const SEASON_TOP: usize = 10;
+
+/// Parse a YYYY-MM-DD date into the unix time of its midnight in UTC
+pub fn parse_date(s: &str) -> Result<u64, String> {
+    let parts: Vec<&str> = s.split('-').collect();
+    let (year, month, day) = match parts[..] {
+        [year, month, day] => (year, month, day),
+        _ => return Err(format!("Expected YYYY-MM-DD, got {:?}", s)),
+    };
+    let year: i64 = year.parse().map_err(|e| format!("Bad year: {}", e))?;
+    let month: i64 = month.parse().map_err(|e| format!("Bad month: {}", e))?;
+    let day: i64 = day.parse().map_err(|e| format!("Bad day: {}", e))?;
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=12).contains(&month) || !(1..=month_days).contains(&day) || year < 1970 {
+        return Err(format!("{:?} is not a date", s));
+    }
+    // Days since the epoch in the proleptic gregorian calendar
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    Ok(days as u64 * 24 * 60 * 60)
+}
+
+/// Format a unix time as the YYYY-MM-DD date it falls on in UTC
+pub fn format_date(time: u64) -> String {
+    let days = (time / (24 * 60 * 60)) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Everything a season left behind, written once when it ends
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeasonArchive {
+    pub number: usize,
+    pub start: u64,
+    pub end: u64,
+    pub highscores: Vec<LeaderboardEntry>,
+    pub ratings: Vec<LeaderboardEntry>,
+    pub stats: HashMap<String, PlayerStats>,
+}
+
+impl SeasonArchive {
+    pub fn summary(&self, finished: usize) -> SeasonSummary {
+        SeasonSummary {
+            number: self.number,
+            start: self.start,
+            end: self.end,
+            top: self.highscores.iter().take(SEASON_TOP).cloned().collect(),
+            finished,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrentSeason {
+    pub number: usize,
+    pub start: u64,
+}
+
+impl Default for CurrentSeason {
+    fn default() -> Self {
+        Self {
+            number: 1,
+            start: unix_time(),
+        }
+    }
+}
+
+pub struct Seasons {
+    /// Configured end dates, a season ends at the first one after it started
+    pub ends: Vec<u64>,
+    pub current: CurrentSeason,
+}
+
+impl Seasons {
+    pub fn dir() -> std::path::PathBuf {
+        state_path("SEASONS", "seasons")
+    }
+    fn current_path() -> std::path::PathBuf {
+        Self::dir().join("current.json")
+    }
+    fn archive_path(number: usize) -> std::path::PathBuf {
+        Self::dir().join(format!("{}.json", number))
+    }
+    pub fn load(ends: Vec<u64>) -> anyhow::Result<Self> {
+        let path = Self::current_path();
+        let current = if path.is_file() {
+            read_json(&path)?
+        } else {
+            // Remember when the first season started so restarts don't push it forward
+            let current = CurrentSeason::default();
+            Self::save_current(&current)?;
+            current
+        };
+        Ok(Self { ends, current })
+    }
+    fn save_current(current: &CurrentSeason) -> anyhow::Result<()> {
+        std::fs::create_dir_all(Self::dir())
+            .with_context(|| format!("Failed to create {:?}", Self::dir()))?;
+        write_json_atomic(&Self::current_path(), current)
+    }
+    /// Number of seasons that have been archived
+    pub fn finished(&self) -> usize {
+        self.current.number - 1
+    }
+    /// End of the current season if it has passed, the earliest one when several have
+    pub fn due(&self) -> Option<u64> {
+        let now = unix_time();
+        self.ends
+            .iter()
+            .copied()
+            .filter(|&end| end > self.current.start && end <= now)
+            .min()
+    }
+    pub fn load_archive(number: usize) -> anyhow::Result<SeasonArchive> {
+        read_json(&Self::archive_path(number))
+    }
+    /// Every archived season in order, without touching the current one
+    pub fn load_archives() -> anyhow::Result<Vec<SeasonArchive>> {
+        let mut archives = Vec::new();
+        while Self::archive_path(archives.len() + 1).is_file() {
+            archives.push(Self::load_archive(archives.len() + 1)?);
+        }
+        Ok(archives)
+    }
+    /// Archive the current season and start the next one where it ended
+    pub fn finish(&mut self, archive: &SeasonArchive) -> anyhow::Result<()> {
+        std::fs::create_dir_all(Self::dir())
+            .with_context(|| format!("Failed to create {:?}", Self::dir()))?;
+        write_json_atomic(&Self::archive_path(archive.number), archive)?;
+        let next = CurrentSeason {
+            number: archive.number + 1,
+            start: archive.end,
+        };
+        Self::save_current(&next)?;
+        self.current = next;
+        Ok(())
+    }
+    pub fn summary(&self, number: Option<usize>) -> Option<SeasonSummary> {
+        let number = number.unwrap_or(self.finished());
+        if number == 0 || number > self.finished() {
+            return None;
+        }
+        match Self::load_archive(number) {
+            Ok(archive) => Some(archive.summary(self.finished())),
+            Err(e) => {
+                log::error!("{:#}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_dates() {
+        assert_eq!(parse_date("1970-01-01"), Ok(0));
+        assert_eq!(parse_date("2000-03-01"), Ok(951868800));
+        assert_eq!(parse_date("2026-10-18"), Ok(1792281600));
+        assert_eq!(format_date(1792281600 + 12 * 60 * 60), "2026-10-18");
+    }
+
+    #[test]
+    fn dates_round_trip() {
+        let day = 24 * 60 * 60;
+        // Every day from 1970 until well past 2100, across leap and century years
+        for days in 0..60000 {
+            let date = format_date(days * day);
+            assert_eq!(parse_date(&date), Ok(days * day), "{}", date);
+        }
+    }
+
+    #[test]
+    fn impossible_dates_are_rejected() {
+        for date in [
+            "2026-02-29",
+            "2026-02-31",
+            "2026-04-31",
+            "2100-02-29",
+            "2026-13-01",
+            "2026-00-10",
+            "2026-01-00",
+            "1969-12-31",
+            "2026-01",
+        ] {
+            assert!(parse_date(date).is_err(), "{}", date);
+        }
+        assert!(parse_date("2024-02-29").is_ok());
+        assert!(parse_date("2000-02-29").is_ok());
+    }
+}
@@ -0,0 +1,29 @@
+use super::*;
+
+pub const SNAPSHOT_INTERVAL: f32 = 5.0;
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub shared: SharedModel,
+    pub track_seed: u64,
+    #[serde(default)]
+    pub race: Option<Race>,
+}
+
+impl Snapshot {
+    pub fn path() -> std::path::PathBuf {
+        state_path("SNAPSHOT", "snapshot.json")
+    }
+    pub fn load() -> Option<Self> {
+        match read_json_or_default(&Self::path()) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                log::error!("Failed to load snapshot: {:#}", e);
+                None
+            }
+        }
+    }
+    pub fn save(&self) -> anyhow::Result<()> {
+        write_json_atomic(&Self::path(), self)
+    }
+}
@@ -0,0 +1,166 @@
+use super::*;
+
+/// How far a single race can move a rating
+const RATING_K: f32 = 32.0;
+
+/// Combine the stats of two names that turned out to be the same player
+fn merge_stats(stats: &mut PlayerStats, other: PlayerStats) {
+    stats.races_started += other.races_started;
+    stats.races_finished += other.races_finished;
+    stats.total_distance += other.total_distance;
+    stats.total_score += other.total_score;
+    for (cause, count) in other.crashes {
+        *stats.crashes.entry(cause).or_default() += count;
+    }
+    stats.best_place = match (stats.best_place, other.best_place) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    stats.rating = stats.rating.or(other.rating);
+}
+
+pub struct Stats {
+    pub players: HashMap<String, PlayerStats>,
+    dirty: bool,
+}
+
+impl Stats {
+    pub fn path() -> std::path::PathBuf {
+        state_path("STATS", "stats.json")
+    }
+    pub fn load() -> anyhow::Result<Self> {
+        let mut stats = Self {
+            players: read_json_or_default(&Self::path())?,
+            dirty: false,
+        };
+        stats.dirty = normalize_keys(&mut stats.players, merge_stats);
+        stats.save();
+        Ok(stats)
+    }
+    /// Write the stats if anything changed since the last save
+    pub fn save(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        match write_json_atomic(&Self::path(), &self.players) {
+            Ok(()) => self.dirty = false,
+            Err(e) => log::error!("Failed to save player stats: {:#}", e),
+        }
+    }
+    fn player(&mut self, name: &str) -> &mut PlayerStats {
+        self.dirty = true;
+        self.players.entry(name.to_owned()).or_default()
+    }
+    /// Rounded rating, None for players who have not been rated yet
+    pub fn rating(&self, name: &str) -> Option<i32> {
+        let rating = self.players.get(name)?.rating?;
+        Some(rating.round() as i32)
+    }
+    pub fn ratings(&self) -> HashMap<String, i32> {
+        self.players
+            .keys()
+            .filter_map(|name| Some((name.clone(), self.rating(name)?)))
+            .collect()
+    }
+    pub fn clear(&mut self) {
+        self.players.clear();
+        self.dirty = true;
+        self.save();
+    }
+    pub fn race_started(&mut self, name: &str) {
+        self.player(name).races_started += 1;
+    }
+    pub fn crashed(&mut self, name: &str, cause: CrashCause) {
+        *self.player(name).crashes.entry(cause).or_default() += 1;
+    }
+    pub fn race_finished(&mut self, record: &RaceRecord) {
+        for result in &record.results {
+            let stats = self.player(&result.name);
+            stats.races_finished += 1;
+            stats.total_distance += result.distance;
+            stats.total_score += result.score as i64;
+            stats.best_place = Some(match stats.best_place {
+                Some(place) => place.min(result.place),
+                None => result.place,
+            });
+        }
+        self.update_ratings(&record.results);
+        self.save();
+    }
+    /// Elo over every pair of players in the race, so beating strong players counts more.
+    /// Solo races leave the rating alone.
+    fn update_ratings(&mut self, results: &[PlayerResult]) {
+        if results.len() < 2 {
+            return;
+        }
+        let ratings: Vec<f32> = results
+            .iter()
+            .map(|result| {
+                self.players
+                    .get(&result.name)
+                    .map_or(INITIAL_RATING, |stats| stats.rating())
+            })
+            .collect();
+        let k = RATING_K / (results.len() - 1) as f32;
+        for (index, result) in results.iter().enumerate() {
+            let mut delta = 0.0;
+            for (other_index, other) in results.iter().enumerate() {
+                if other_index == index {
+                    continue;
+                }
+                let expected =
+                    1.0 / (1.0 + 10f32.powf((ratings[other_index] - ratings[index]) / 400.0));
+                let actual = match result.score.cmp(&other.score) {
+                    std::cmp::Ordering::Greater => 1.0,
+                    std::cmp::Ordering::Equal => 0.5,
+                    std::cmp::Ordering::Less => 0.0,
+                };
+                delta += k * (actual - expected);
+            }
+            self.player(&result.name).rating = Some(ratings[index] + delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(name: &str, place: usize, score: i32) -> PlayerResult {
+        PlayerResult {
+            name: name.to_owned(),
+            place,
+            score,
+            distance: score as f32 / 100.0,
+            crashes: 0,
+        }
+    }
+
+    #[test]
+    fn two_player_elo() {
+        let mut stats = Stats {
+            players: default(),
+            dirty: false,
+        };
+        let race = [result("winner", 1, 2000), result("loser", 2, 1000)];
+        stats.update_ratings(&race);
+        assert_eq!(stats.rating("winner"), Some(1516));
+        assert_eq!(stats.rating("loser"), Some(1484));
+
+        // The favourite gains less for winning again, and points are only moved around
+        stats.update_ratings(&race);
+        let winner = stats.players["winner"].rating();
+        let loser = stats.players["loser"].rating();
+        assert!((winner - 1516.0 - 14.53).abs() < 0.01, "{}", winner);
+        assert!((winner + loser - 2.0 * INITIAL_RATING).abs() < 0.01);
+
+        // A tie between equal players changes nothing
+        let mut stats = Stats {
+            players: default(),
+            dirty: false,
+        };
+        stats.update_ratings(&[result("a", 1, 1000), result("b", 1, 1000)]);
+        assert_eq!(stats.rating("a"), Some(1500));
+        assert_eq!(stats.rating("b"), Some(1500));
+    }
+}
@@ -0,0 +1,151 @@
+use super::*;
+
+const TOP_HIGHSCORES: usize = 10;
+
+#[derive(Serialize)]
+struct Status {
+    players: Vec<String>,
+    spectators: usize,
+    race_running: bool,
+    avalanche_position: Option<f32>,
+    scores: Vec<(String, i32)>,
+    highscores: Vec<LeaderboardEntry>,
+    winner: Option<(String, i32)>,
+}
+
+impl Model {
+    fn status(&self) -> Status {
+        let mut highscores = self.ranked(Board::AllTime);
+        highscores.truncate(TOP_HIGHSCORES);
+        Status {
+            players: self
+                .shared
+                .players
+                .iter()
+                .map(|player| player.name.clone())
+                .collect(),
+            spectators: self
+                .clients
+                .values()
+                .filter(|client| client.role == Role::Spectator)
+                .count(),
+            race_running: self.shared.avalanche_position.is_some(),
+            avalanche_position: self.shared.avalanche_position,
+            scores: self.sorted_scores(),
+            highscores,
+            winner: self.shared.winner.clone(),
+        }
+    }
+}
+
+fn metrics_text(handle: &simple_net::ServerHandle<Model>) -> String {
+    let metrics = handle.metrics();
+    let (players, race_running) = handle.update(|model, _| {
+        (
+            model.shared.players.len(),
+            model.shared.avalanche_position.is_some(),
+        )
+    });
+    let mut text = String::new();
+    let mut metric = |name: &str, kind: &str, value: String| {
+        text.push_str(&format!("# TYPE {} {}\n{} {}\n", name, kind, name, value));
+    };
+    metric("ld50_ticks_total", "counter", metrics.ticks.to_string());
+    metric(
+        "ld50_tick_duration_seconds",
+        "gauge",
+        metrics.last_tick_seconds.to_string(),
+    );
+    metric(
+        "ld50_tick_duration_seconds_total",
+        "counter",
+        metrics.total_tick_seconds.to_string(),
+    );
+    metric(
+        "ld50_sent_bytes_total",
+        "counter",
+        metrics.bytes_sent.to_string(),
+    );
+    metric(
+        "ld50_update_bytes",
+        "gauge",
+        metrics.last_update_bytes.to_string(),
+    );
+    metric(
+        "ld50_connections_total",
+        "counter",
+        metrics.connections_total.to_string(),
+    );
+    metric("ld50_connections", "gauge", metrics.connections.to_string());
+    metric("ld50_clients", "gauge", metrics.players.to_string());
+    metric("ld50_players", "gauge", players.to_string());
+    metric(
+        "ld50_race_running",
+        "gauge",
+        (race_running as i32).to_string(),
+    );
+    text
+}
+
+fn respond(
+    stream: &mut std::net::TcpStream,
+    handle: &simple_net::ServerHandle<Model>,
+) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    std::io::BufRead::read_line(
+        &mut std::io::BufReader::new(&mut *stream),
+        &mut request_line,
+    )?;
+    let mut parts = request_line.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/status")) => (
+            "200 OK",
+            "application/json",
+            serde_json::to_string(&handle.update(|model, _| model.status())).unwrap(),
+        ),
+        (Some("GET"), Some("/metrics")) => {
+            ("200 OK", "text/plain; version=0.0.4", metrics_text(handle))
+        }
+        _ => ("404 Not Found", "text/plain", "Not found".to_owned()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body,
+    );
+    std::io::Write::write_all(stream, response.as_bytes())
+}
+
+pub fn run_status_server(addr: &str, handle: simple_net::ServerHandle<Model>) {
+    let listener = match std::net::TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Failed to start status server on {}: {}", addr, e);
+            return;
+        }
+    };
+    log::info!("Status server listening on {}", addr);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Status request failed: {}", e);
+                    continue;
+                }
+            };
+            // A slow client only holds up its own request, not the next scrape
+            let handle = handle.clone();
+            std::thread::spawn(move || {
+                let result = stream
+                    .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+                    .and_then(|()| respond(&mut stream, &handle));
+                if let Err(e) = result {
+                    log::warn!("Status request failed: {}", e);
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,198 @@
+use super::*;
+use anyhow::Context as _;
+
+pub fn tournament_path() -> std::path::PathBuf {
+    state_path("TOURNAMENT", "tournament.json")
+}
+
+pub fn load_tournament() -> anyhow::Result<Option<Tournament>> {
+    read_json_or_default(&tournament_path())
+}
+
+impl Model {
+    fn save_tournament(&self) {
+        let path = tournament_path();
+        let result = match &self.shared.tournament {
+            Some(tournament) => write_json_atomic(&path, tournament),
+            None if path.is_file() => {
+                std::fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))
+            }
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            log::error!("Failed to save tournament: {:#}", e);
+        }
+    }
+    /// Seed for the next track, the tournament's own while it has heats left
+    pub fn next_track_seed(&self) -> u64 {
+        match &self.shared.tournament {
+            Some(tournament) if !tournament.is_finished() => tournament.seed,
+            _ => thread_rng().gen(),
+        }
+    }
+    pub fn new_tournament(
+        &mut self,
+        name: &str,
+        heats: usize,
+        scoring: Scoring,
+    ) -> Result<(), String> {
+        if let Some(tournament) = &self.shared.tournament {
+            if !tournament.is_finished() {
+                return Err(format!(
+                    "{} is still running, end it first",
+                    tournament.name
+                ));
+            }
+        }
+        let name: String = name
+            .to_lowercase()
+            .chars()
+            .filter(|&c| c == ' ' || NAME_CHARS.contains(c))
+            .collect();
+        self.shared.tournament = Some(Tournament {
+            name: if name.trim().is_empty() {
+                "tournament".to_owned()
+            } else {
+                name.trim().to_owned()
+            },
+            seed: thread_rng().gen(),
+            heats,
+            scoring,
+            participants: Vec::new(),
+            armed: false,
+            results: Vec::new(),
+        });
+        self.save_tournament();
+        if self.shared.avalanche_position.is_none() {
+            self.reset_race();
+        }
+        Ok(())
+    }
+    /// Make the next race that starts count as a heat
+    pub fn arm_heat(&mut self) -> Result<usize, String> {
+        let tournament = match &mut self.shared.tournament {
+            Some(tournament) if !tournament.is_finished() => tournament,
+            Some(_) => return Err("The tournament is over".to_owned()),
+            None => return Err("No tournament is running".to_owned()),
+        };
+        if tournament.participants.is_empty() {
+            return Err("Register participants with tournament add first".to_owned());
+        }
+        tournament.armed = true;
+        let heat = tournament.results.len() + 1;
+        let seed = tournament.seed;
+        self.save_tournament();
+        // A track from before the tournament, like one restored from a snapshot, is replaced
+        // so the heat isn't raced on it
+        if self.shared.avalanche_position.is_none() && self.track_gen.seed() != seed {
+            log::info!("Tournament: resetting the track to the tournament seed");
+            self.reset_race();
+        }
+        Ok(heat)
+    }
+    /// Whether the race that is starting is a heat, a heat is only armed for one race.
+    /// A race on another track leaves the heat armed for the next one
+    pub fn take_armed_heat(&mut self) -> bool {
+        let seed = self.track_gen.seed();
+        let armed = match &mut self.shared.tournament {
+            Some(tournament) if !tournament.is_finished() && tournament.armed => {
+                if tournament.seed != seed {
+                    log::warn!(
+                        "Tournament: the track is not the tournament's, the heat stays armed"
+                    );
+                    return false;
+                }
+                tournament.armed = false;
+                true
+            }
+            _ => false,
+        };
+        if armed {
+            self.save_tournament();
+        }
+        armed
+    }
+    pub fn end_tournament(&mut self) -> bool {
+        if self.shared.tournament.take().is_none() {
+            return false;
+        }
+        self.save_tournament();
+        true
+    }
+    pub fn register_participant(&mut self, name: &str) -> Result<String, String> {
+        let name = normalize_name(name);
+        let tournament = self
+            .shared
+            .tournament
+            .as_mut()
+            .ok_or("No tournament is running")?;
+        if name.is_empty() {
+            return Err("Empty name".to_owned());
+        }
+        if tournament.participants.contains(&name) {
+            return Err(format!("{:?} is already registered", name));
+        }
+        tournament.participants.push(name.clone());
+        self.save_tournament();
+        Ok(name)
+    }
+    pub fn unregister_participant(&mut self, name: &str) -> bool {
+        let name = normalize_name(name);
+        let tournament = match &mut self.shared.tournament {
+            Some(tournament) => tournament,
+            None => return false,
+        };
+        let count = tournament.participants.len();
+        tournament
+            .participants
+            .retain(|participant| *participant != name);
+        if tournament.participants.len() == count {
+            return false;
+        }
+        self.save_tournament();
+        true
+    }
+    /// Count a finished heat if any registered participant finished it
+    pub fn record_heat(&mut self, record: &RaceRecord, events: &mut Vec<Event>) {
+        let tournament = match &mut self.shared.tournament {
+            Some(tournament) if !tournament.is_finished() => tournament,
+            _ => return,
+        };
+        if record.seed != tournament.seed {
+            log::warn!(
+                "Tournament: heat {} was raced on another track, not counting it",
+                record.id
+            );
+            return;
+        }
+        let heat: HashMap<String, i32> = record
+            .results
+            .iter()
+            .filter(|result| tournament.participants.contains(&result.name))
+            .map(|result| (result.name.clone(), result.score))
+            .collect();
+        if heat.is_empty() {
+            log::info!("Tournament: no participant finished the heat, it has to be raced again");
+            events.push(Event::Announcement(
+                "no participant finished the heat".to_owned(),
+            ));
+            return;
+        }
+        tournament.results.push(heat);
+        let text = if tournament.is_finished() {
+            match tournament.standings().first() {
+                Some(winner) => format!("{} won the {}", winner.name, tournament.name),
+                None => format!("{} is over", tournament.name),
+            }
+        } else {
+            format!(
+                "heat {} of {} done",
+                tournament.results.len(),
+                tournament.heats
+            )
+        };
+        log::info!("Tournament: {}", text);
+        events.push(Event::Announcement(text));
+        self.save_tournament();
+    }
+}
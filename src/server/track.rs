@@ -2,36 +2,55 @@ use super::*;
 
 pub struct TrackGen {
     config: TrackConfig,
+    seed: u64,
     rng: Box<dyn RngCore + Send>,
     last: Vec<vec2<f32>>,
     last_len: [f32; 2],
     obstacle_options: Vec<(usize, ObstacleConfig)>,
 }
 
+pub fn load_obstacles() -> anyhow::Result<Vec<ObstacleConfig>> {
+    let list: Vec<String> = read_json(&data_dir().join("obstacles.json"))?;
+    let mut validator = Validator::default();
+    let mut obstacles = Vec::new();
+    for path in list {
+        // Keep going so every broken obstacle is reported at once
+        match read_json::<ObstacleConfig>(&data_dir().join(format!("{}.json", path))) {
+            Ok(obstacle) => {
+                validator.field(&path, &obstacle);
+                obstacles.push(obstacle);
+            }
+            Err(e) => validator.error(&path, format!("{:#}", e)),
+        }
+    }
+    if obstacles
+        .iter()
+        .map(|obstacle| obstacle.spawn_weight)
+        .sum::<f32>()
+        <= 0.0
+    {
+        validator.error("", "total spawn_weight of obstacles must be positive");
+    }
+    validator.finish("obstacles")?;
+    Ok(obstacles)
+}
+
 impl TrackGen {
-    pub fn new(config: &TrackConfig) -> Self {
-        let list: Vec<String> = serde_json::from_reader(
-            std::fs::File::open(assets_path().join("obstacles.json")).unwrap(),
-        )
-        .unwrap();
-        let obstacle_options: Vec<(usize, ObstacleConfig)> = list
-            .into_iter()
-            .map(|path| {
-                serde_json::from_reader(
-                    std::fs::File::open(assets_path().join(format!("{}.json", path))).unwrap(),
-                )
-                .unwrap()
-            })
-            .enumerate()
-            .collect();
+    pub fn new(config: &TrackConfig, obstacles: &[ObstacleConfig], seed: u64) -> Self {
+        let obstacle_options: Vec<(usize, ObstacleConfig)> =
+            obstacles.iter().cloned().enumerate().collect();
         Self {
             config: config.clone(),
-            rng: Box::new(StdRng::from_seed(thread_rng().gen())),
+            seed,
+            rng: Box::new(StdRng::seed_from_u64(seed)),
             last: vec![vec2::ZERO, vec2::ZERO],
             last_len: [0.0; 2],
             obstacle_options,
         }
     }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
     pub fn init(&self) -> Track {
         Track {
             obstacles: vec![],
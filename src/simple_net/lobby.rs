@@ -1,57 +1,176 @@
 use super::*;
 
-type Connection<T> = net::client::Connection<ServerMessage<T>, <T as Model>::Message>;
+const RECONNECT_DELAY: f64 = 1.0;
 
-pub struct ConnectingState<T: Model, G: geng::State> {
+type Connection<T> = net::client::Connection<ServerMessage<T>, ClientMessage<T>>;
+
+struct Accepted<T: Protocol> {
+    player_id: T::PlayerId,
+    handshake: T::Handshake,
+    rates: Rates,
+    initial_state: T::SharedState,
+    compression: CompressionStats,
+    connection: Connection<T>,
+}
+
+enum ConnectError {
+    Rejected(String),
+    Failed(String),
+}
+
+type ConnectionFuture<T> = Pin<Box<dyn Future<Output = Result<Accepted<T>, ConnectError>>>>;
+
+fn connect<T: Protocol>(addr: &str, handshake: T::Handshake) -> ConnectionFuture<T> {
+    Box::pin(net::client::connect(addr).then(|connection| async move {
+        let mut connection: Connection<T> = match connection {
+            Ok(connection) => connection,
+            Err(e) => return Err(ConnectError::Failed(e.to_string())),
+        };
+        connection.send(ClientMessage::Handshake {
+            payload: handshake,
+            compression: true,
+        });
+        let closed = || ConnectError::Failed("Connection closed".to_owned());
+        let (message, connection) = connection.into_future().await;
+        let (player_id, handshake, rates) = match message.transpose() {
+            Ok(Some(ServerMessage::Welcome {
+                player_id,
+                handshake,
+                compression,
+                rates,
+            })) => {
+                log::info!(
+                    "Connected, compression enabled: {}, rates: {:?}",
+                    compression,
+                    rates,
+                );
+                (player_id, handshake, rates)
+            }
+            Ok(Some(ServerMessage::Rejected(reason))) => {
+                return Err(ConnectError::Rejected(reason));
+            }
+            Ok(Some(_)) => unreachable!(),
+            Ok(None) | Err(_) => return Err(closed()),
+        };
+        let (message, connection) = connection.into_future().await;
+        let mut compression = CompressionStats::default();
+        let message = match message.transpose() {
+            Ok(Some(ServerMessage::Compressed(data))) => {
+                let (raw_len, message) = decompress(&data).unwrap();
+                compression.compressed_bytes += data.len();
+                compression.uncompressed_bytes += raw_len;
+                message
+            }
+            Ok(Some(message)) => message,
+            Ok(None) | Err(_) => return Err(closed()),
+        };
+        let initial_state = match message {
+            ServerMessage::Full(state) => state,
+            _ => unreachable!(),
+        };
+        Ok(Accepted {
+            player_id,
+            handshake,
+            rates,
+            initial_state,
+            compression,
+            connection,
+        })
+    }))
+}
+
+fn shutdown_text(notice: &ShutdownNotice) -> String {
+    let mut text = "Server is restarting".to_owned();
+    if let Some(reason) = &notice.reason {
+        text.push_str(": ");
+        text.push_str(reason);
+    }
+    if let Some(eta) = notice.restart_eta {
+        text.push_str(&format!(", back in about {} seconds", eta.ceil() as i64));
+    }
+    text
+}
+
+pub struct ConnectingState<T: Protocol, G: geng::State> {
     geng: Geng,
+    addr: String,
+    handshake: T::Handshake,
+    connection: Option<ConnectionFuture<T>>,
     #[allow(clippy::type_complexity)]
-    connection: Option<Pin<Box<dyn Future<Output = (T::PlayerId, T::SharedState, Connection<T>)>>>>,
-    #[allow(clippy::type_complexity)]
-    f: Option<Box<dyn FnOnce(T::PlayerId, Remote<T>) -> G + 'static>>,
+    f: Box<dyn FnMut(T::PlayerId, T::Handshake, Remote<T>) -> G + 'static>,
+    remote: Option<Remote<T>>,
+    status: Option<String>,
+    reconnect_timer: Option<f64>,
     transition: Option<geng::state::Transition>,
 }
 
-impl<T: Model, G: geng::State> ConnectingState<T, G> {
+impl<T: Protocol, G: geng::State> ConnectingState<T, G> {
     pub fn new(
         geng: &Geng,
         addr: &str,
-        f: impl FnOnce(T::PlayerId, Remote<T>) -> G + 'static,
+        handshake: T::Handshake,
+        f: impl FnMut(T::PlayerId, T::Handshake, Remote<T>) -> G + 'static,
     ) -> Self {
-        let connection = Box::pin(net::client::connect(addr).then(|connection| async move {
-            let connection = connection.unwrap();
-            let (message, connection) = connection.into_future().await;
-            let player_id = match message.transpose().unwrap() {
-                Some(ServerMessage::PlayerId(id)) => id,
-                _ => unreachable!(),
-            };
-            let (message, connection) = connection.into_future().await;
-            let initial_state = match message.transpose().unwrap() {
-                Some(ServerMessage::Full(state)) => state,
-                _ => unreachable!(),
-            };
-            (player_id, initial_state, connection)
-        }));
         Self {
             geng: geng.clone(),
-            f: Some(Box::new(f)),
-            connection: Some(connection),
+            addr: addr.to_owned(),
+            connection: Some(connect(addr, handshake.clone())),
+            handshake,
+            f: Box::new(f),
+            remote: None,
+            status: None,
+            reconnect_timer: None,
             transition: None,
         }
     }
 }
 
-impl<T: Model, G: geng::State> geng::State for ConnectingState<T, G> {
+impl<T: Protocol, G: geng::State> geng::State for ConnectingState<T, G> {
+    fn update(&mut self, delta_time: f64) {
+        if let Some(remote) = self.remote.take() {
+            // We are back on top, so the game has been closed
+            if let Some(reason) = remote.kick_reason() {
+                self.status = Some(format!("Kicked from the server: {}", reason));
+            } else if let Some(notice) = remote.shutdown_notice() {
+                self.status = Some(shutdown_text(&notice));
+                self.reconnect_timer = Some(RECONNECT_DELAY);
+            } else {
+                // The player left on purpose, don't bring them back
+                self.transition = Some(geng::state::Transition::Pop);
+            }
+        }
+        if let Some(timer) = &mut self.reconnect_timer {
+            *timer -= delta_time;
+            if *timer < 0.0 {
+                self.reconnect_timer = None;
+                self.connection = Some(connect(&self.addr, self.handshake.clone()));
+            }
+        }
+    }
     fn draw(&mut self, framebuffer: &mut ugli::Framebuffer) {
         let framebuffer_size = framebuffer.size();
         ugli::clear(framebuffer, Some(Rgba::WHITE), None, None);
-        self.geng.default_font().draw(
-            framebuffer,
-            &geng::PixelPerfectCamera,
-            "Connecting to the server...",
-            vec2::splat(geng::TextAlign::CENTER),
-            mat3::translate(framebuffer_size.map(|x| x as f32) / 2.0) * mat3::scale_uniform(40.0),
-            Rgba::BLACK,
-        );
+        let center = framebuffer_size.map(|x| x as f32) / 2.0;
+        if let Some(status) = &self.status {
+            self.geng.default_font().draw(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                status,
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(center + vec2(0.0, 60.0)) * mat3::scale_uniform(40.0),
+                Rgba::BLACK,
+            );
+        }
+        if self.connection.is_some() || self.reconnect_timer.is_some() {
+            self.geng.default_font().draw(
+                framebuffer,
+                &geng::PixelPerfectCamera,
+                "Connecting to the server...",
+                vec2::splat(geng::TextAlign::CENTER),
+                mat3::translate(center) * mat3::scale_uniform(40.0),
+                Rgba::BLACK,
+            );
+        }
     }
     fn handle_event(&mut self, event: geng::Event) {
         if matches!(
@@ -65,22 +184,42 @@ impl<T: Model, G: geng::State> geng::State for ConnectingState<T, G> {
     }
     fn transition(&mut self) -> Option<geng::state::Transition> {
         if let Some(connection) = &mut self.connection {
-            if let std::task::Poll::Ready((player_id, initial_state, connection)) = connection
-                .as_mut()
-                .poll(&mut std::task::Context::from_waker(
-                    futures::task::noop_waker_ref(),
-                ))
+            if let std::task::Poll::Ready(result) =
+                connection
+                    .as_mut()
+                    .poll(&mut std::task::Context::from_waker(
+                        futures::task::noop_waker_ref(),
+                    ))
             {
-                return Some(geng::state::Transition::Switch(Box::new(self
-                    .f
-                    .take()
-                    .unwrap()(
-                    player_id,
-                    Remote {
-                        connection: Rc::new(RefCell::new(connection)),
-                        model: Rc::new(RefCell::new(initial_state)),
-                    },
-                ))));
+                self.connection = None;
+                match result {
+                    Ok(accepted) => {
+                        T::reconnect_handshake(&mut self.handshake, &accepted.handshake);
+                        let remote = Remote {
+                            connection: Rc::new(RefCell::new(accepted.connection)),
+                            model: Rc::new(RefCell::new(accepted.initial_state)),
+                            rates: accepted.rates,
+                            compression: Rc::new(Cell::new(accepted.compression)),
+                            shutdown: Rc::new(RefCell::new(None)),
+                            kicked: Rc::new(RefCell::new(None)),
+                        };
+                        self.remote = Some(remote.clone());
+                        self.status = None;
+                        return Some(geng::state::Transition::Push(Box::new((self.f)(
+                            accepted.player_id,
+                            accepted.handshake,
+                            remote,
+                        ))));
+                    }
+                    Err(ConnectError::Rejected(reason)) => {
+                        log::warn!("Connection rejected: {}", reason);
+                        self.status = Some(format!("Connection rejected: {}", reason));
+                    }
+                    Err(ConnectError::Failed(e)) => {
+                        log::warn!("Failed to connect: {}", e);
+                        self.reconnect_timer = Some(RECONNECT_DELAY);
+                    }
+                }
             }
         }
         self.transition.take()
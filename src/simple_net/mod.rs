@@ -1,22 +1,40 @@
-use geng::net;
+#[cfg(not(feature = "client"))]
+use batbox::prelude::*;
+#[cfg(feature = "client")]
 use geng::prelude::*;
+use geng_net as net;
 
+#[cfg(feature = "client")]
 mod lobby;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod server;
 
+#[cfg(feature = "client")]
 pub use lobby::*;
-#[cfg(not(target_arch = "wasm32"))]
-pub mod server;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
 pub use server::*;
 
-pub trait Model: 'static + Send {
+pub trait Protocol: 'static {
     type SharedState: Diff<Delta: net::Message + Clone> + net::Message + PartialEq + Clone;
-    fn shared_state(&self) -> &Self::SharedState;
-    type PlayerId: net::Message + Clone;
+    type PlayerId: net::Message + Clone + PartialEq;
     type Message: net::Message;
     type Event: net::Message + Clone;
-    const TICKS_PER_SECOND: f32;
-    fn new_player(&mut self, events: &mut Vec<Self::Event>) -> Self::PlayerId;
+    type Handshake: net::Message + Clone;
+    /// Events meant for a single player are only sent to them
+    fn event_recipient(_event: &Self::Event) -> Option<&Self::PlayerId> {
+        None
+    }
+    /// Carries over what the server handed out (like credentials) when reconnecting
+    fn reconnect_handshake(_requested: &mut Self::Handshake, _accepted: &Self::Handshake) {}
+}
+
+pub trait Model: Protocol + Send {
+    fn shared_state(&self) -> &Self::SharedState;
+    fn new_player(
+        &mut self,
+        events: &mut Vec<Self::Event>,
+        handshake: Self::Handshake,
+    ) -> Result<(Self::PlayerId, Self::Handshake), String>;
     fn drop_player(&mut self, events: &mut Vec<Self::Event>, player_id: &Self::PlayerId);
     fn handle_message(
         &mut self,
@@ -24,35 +42,130 @@ pub trait Model: 'static + Send {
         player_id: &Self::PlayerId,
         message: Self::Message,
     );
-    fn tick(&mut self, events: &mut Vec<Self::Event>);
+    fn tick(&mut self, events: &mut Vec<Self::Event>, delta_time: f32);
+    fn shutdown(&mut self);
+    /// Checked before a connection from this address is accepted
+    fn allow_address(&self, _addr: &std::net::IpAddr) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Rates {
+    pub ticks_per_second: f32,
+    pub updates_per_second: f32,
+}
+
+impl Default for Rates {
+    fn default() -> Self {
+        Self {
+            ticks_per_second: 10.0,
+            updates_per_second: 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShutdownNotice {
+    pub reason: Option<String>,
+    pub restart_eta: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Derivative)]
-#[derivative(Debug(bound = ""))]
-pub enum ServerMessage<T: Model> {
-    PlayerId(T::PlayerId),
+#[derivative(Debug(bound = ""), Clone(bound = ""))]
+pub enum ServerMessage<T: Protocol> {
+    Welcome {
+        player_id: T::PlayerId,
+        handshake: T::Handshake,
+        compression: bool,
+        rates: Rates,
+    },
+    Rejected(String),
     Delta(#[serde(bound = "")] <T::SharedState as Diff>::Delta),
     Full(#[serde(bound = "")] T::SharedState),
     Events(Vec<T::Event>),
+    Compressed(Vec<u8>),
+    Shutdown(ShutdownNotice),
+    Kicked(String),
+}
+
+#[derive(Serialize, Deserialize, Derivative)]
+#[derivative(Debug(bound = ""))]
+pub enum ClientMessage<T: Protocol> {
+    Handshake {
+        payload: T::Handshake,
+        compression: bool,
+    },
+    Message(T::Message),
+}
+
+#[cfg(feature = "client")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CompressionStats {
+    pub compressed_bytes: usize,
+    pub uncompressed_bytes: usize,
+}
+
+#[cfg(feature = "client")]
+pub struct Traffic {
+    pub connection: net::Traffic,
+    pub compression: CompressionStats,
 }
 
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+fn compress<M: Serialize>(message: &M) -> (usize, Vec<u8>) {
+    let data = bincode::serialize(message).expect("Failed to serialize message");
+    let mut encoder =
+        flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+    std::io::Write::write_all(&mut encoder, &data).unwrap();
+    (data.len(), encoder.finish().unwrap())
+}
+
+#[cfg(feature = "client")]
+fn decompress<M: serde::de::DeserializeOwned>(data: &[u8]) -> anyhow::Result<(usize, M)> {
+    let mut raw = Vec::new();
+    std::io::Read::read_to_end(&mut flate2::read::DeflateDecoder::new(data), &mut raw)?;
+    Ok((raw.len(), bincode::deserialize(&raw)?))
+}
+
+#[cfg(feature = "client")]
 #[derive(Derivative)]
 #[derivative(Clone(bound = ""))]
-pub struct Remote<T: Model> {
-    connection: Rc<RefCell<net::client::Connection<ServerMessage<T>, T::Message>>>,
+pub struct Remote<T: Protocol> {
+    connection: Rc<RefCell<net::client::Connection<ServerMessage<T>, ClientMessage<T>>>>,
     model: Rc<RefCell<T::SharedState>>,
+    rates: Rates,
+    compression: Rc<Cell<CompressionStats>>,
+    shutdown: Rc<RefCell<Option<ShutdownNotice>>>,
+    kicked: Rc<RefCell<Option<String>>>,
 }
 
-impl<T: Model> Remote<T> {
+#[cfg(feature = "client")]
+impl<T: Protocol> Remote<T> {
     pub fn update(&self) -> Vec<T::Event> {
         let mut model = self.model.borrow_mut();
         let mut events = Vec::new();
         for message in self.connection.borrow_mut().new_messages() {
-            match message.unwrap() {
+            let message = match message.unwrap() {
+                ServerMessage::Compressed(data) => {
+                    let (raw_len, message) = decompress(&data).unwrap();
+                    let mut stats = self.compression.get();
+                    stats.compressed_bytes += data.len();
+                    stats.uncompressed_bytes += raw_len;
+                    self.compression.set(stats);
+                    message
+                }
+                message => message,
+            };
+            match message {
                 ServerMessage::Full(state) => *model = state,
                 ServerMessage::Delta(delta) => model.update(&delta),
-                ServerMessage::PlayerId(_) => unreachable!(),
+                ServerMessage::Welcome { .. }
+                | ServerMessage::Rejected(_)
+                | ServerMessage::Compressed(_) => unreachable!(),
                 ServerMessage::Events(e) => events.extend(e),
+                ServerMessage::Shutdown(notice) => *self.shutdown.borrow_mut() = Some(notice),
+                ServerMessage::Kicked(reason) => *self.kicked.borrow_mut() = Some(reason),
             }
         }
         events
@@ -61,9 +174,26 @@ impl<T: Model> Remote<T> {
         self.model.borrow()
     }
     pub fn send(&self, message: T::Message) {
-        self.connection.borrow_mut().send(message);
+        self.connection
+            .borrow_mut()
+            .send(ClientMessage::Message(message));
+    }
+    pub fn rates(&self) -> Rates {
+        self.rates
     }
-    pub fn traffic(&self) -> net::Traffic {
-        self.connection.borrow().traffic()
+    pub fn shutdown_notice(&self) -> Option<ShutdownNotice> {
+        self.shutdown.borrow().clone()
+    }
+    pub fn kick_reason(&self) -> Option<String> {
+        self.kicked.borrow().clone()
+    }
+    pub fn is_closed(&self) -> bool {
+        self.shutdown.borrow().is_some() || self.kicked.borrow().is_some()
+    }
+    pub fn traffic(&self) -> Traffic {
+        Traffic {
+            connection: self.connection.borrow().traffic(),
+            compression: self.compression.get(),
+        }
     }
 }
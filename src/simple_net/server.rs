@@ -1,48 +1,355 @@
 use super::*;
 
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    pub compression_threshold: Option<usize>,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_address: Option<usize>,
+    pub rates: Rates,
+}
+
 struct ClientState<T: Model> {
     sender: Box<dyn net::Sender<ServerMessage<T>>>,
+    player_id: Option<T::PlayerId>,
+    compression: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ServerMetrics {
+    pub ticks: u64,
+    pub last_tick_seconds: f64,
+    pub total_tick_seconds: f64,
+    pub bytes_sent: u64,
+    pub last_update_bytes: u64,
+    pub connections_total: u64,
+    pub connections: usize,
+    pub players: usize,
+}
+
+/// A message ready to go out, with its serialized sizes measured once for the metrics
+struct Outgoing<T: Model> {
+    message: ServerMessage<T>,
+    size: u64,
+    compressed: Option<(ServerMessage<T>, u64)>,
+}
+
+impl<T: Model> Outgoing<T> {
+    fn new(message: ServerMessage<T>, threshold: Option<usize>, compress: bool) -> Self {
+        let size = bincode::serialized_size(&message).unwrap_or(0);
+        let compressed = match threshold {
+            Some(threshold) if compress && size >= threshold as u64 => {
+                let compressed = ServerMessage::Compressed(super::compress(&message).1);
+                let compressed_size = bincode::serialized_size(&compressed).unwrap_or(0);
+                Some((compressed, compressed_size))
+            }
+            _ => None,
+        };
+        Self {
+            message,
+            size,
+            compressed,
+        }
+    }
+}
+
+impl<T: Model> ClientState<T> {
+    fn send(&mut self, outgoing: &Outgoing<T>) -> u64 {
+        let (message, size) = match &outgoing.compressed {
+            Some((compressed, size)) if self.compression => (compressed, *size),
+            _ => (&outgoing.message, outgoing.size),
+        };
+        self.sender.send(message.clone());
+        size
+    }
 }
 
 struct ServerState<T: Model> {
+    config: ServerConfig,
     current: T,
     previous: T::SharedState,
     events: Vec<T::Event>,
     next_client_id: usize,
     clients: HashMap<usize, ClientState<T>>,
+    peers: Peers,
+    metrics: ServerMetrics,
+}
+
+/// Open connections by remote address, as seen by the forwarding listener
+#[derive(Default)]
+struct Peers {
+    next_id: usize,
+    open: HashMap<std::net::IpAddr, HashMap<usize, std::net::TcpStream>>,
+    /// Forwarded connections that the websocket server has not picked up yet,
+    /// anything it sees beyond these did not come through the listener
+    pending: usize,
+}
+
+impl Peers {
+    fn count(&self, addr: &std::net::IpAddr) -> usize {
+        self.open.get(addr).map_or(0, |streams| streams.len())
+    }
+    fn add(&mut self, addr: std::net::IpAddr, stream: std::net::TcpStream) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.open.entry(addr).or_default().insert(id, stream);
+        id
+    }
+    fn remove(&mut self, addr: &std::net::IpAddr, id: usize) {
+        if let Some(streams) = self.open.get_mut(addr) {
+            streams.remove(&id);
+            if streams.is_empty() {
+                self.open.remove(addr);
+            }
+        }
+        // A connection that closed before its websocket handshake never got picked up
+        let open = self.open.values().map(|streams| streams.len()).sum();
+        self.pending = self.pending.min(open);
+    }
+    fn disconnect(&mut self, addr: &std::net::IpAddr) -> usize {
+        let streams = self.open.remove(addr).unwrap_or_default();
+        for stream in streams.values() {
+            stream.shutdown(std::net::Shutdown::Both).ok();
+        }
+        streams.len()
+    }
+}
+
+/// Longest http request head read while looking for a forwarded address
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Read the start of the websocket upgrade request, returning the bytes read
+/// and the client address a local reverse proxy put in X-Forwarded-For
+fn read_forwarded_for(
+    stream: &mut std::net::TcpStream,
+) -> std::io::Result<(Vec<u8>, Option<std::net::IpAddr>)> {
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+    let mut head = Vec::new();
+    let mut buffer = [0; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let read = std::io::Read::read(stream, &mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    stream.set_read_timeout(None)?;
+    let addr = String::from_utf8_lossy(&head)
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("x-forwarded-for"))
+        .and_then(|(_, value)| value.split(',').next_back()?.trim().parse().ok());
+    Ok((head, addr))
+}
+
+/// Copy one direction of a forwarded connection, closing both ends once either side is done
+fn forward(mut from: std::net::TcpStream, mut to: std::net::TcpStream) {
+    std::io::copy(&mut from, &mut to).ok();
+    from.shutdown(std::net::Shutdown::Both).ok();
+    to.shutdown(std::net::Shutdown::Both).ok();
+}
+
+/// Check a new connection against the bans and limits and forward it to the websocket server
+fn forward_connection<T: Model>(
+    mut stream: std::net::TcpStream,
+    inner: std::net::SocketAddr,
+    state: Arc<Mutex<ServerState<T>>>,
+) -> std::io::Result<()> {
+    let mut addr = stream.peer_addr()?.ip();
+    let mut head = Vec::new();
+    // Behind a reverse proxy on the same host every peer is local,
+    // the proxy tells who is really connecting
+    if addr.is_loopback() {
+        let (read, forwarded_for) = read_forwarded_for(&mut stream)?;
+        head = read;
+        addr = forwarded_for.unwrap_or(addr);
+    }
+    let id = {
+        let mut state = state.lock().unwrap();
+        if !state.current.allow_address(&addr) {
+            log::info!("Refused connection from banned address {}", addr);
+            return Ok(());
+        }
+        if let Some(max) = state.config.max_connections_per_address {
+            if state.peers.count(&addr) >= max {
+                log::info!("Refused connection from {}: too many connections", addr);
+                return Ok(());
+            }
+        }
+        state.peers.pending += 1;
+        state.peers.add(addr, stream.try_clone()?)
+    };
+    let result = std::net::TcpStream::connect(inner).and_then(|mut upstream| {
+        std::io::Write::write_all(&mut upstream, &head)?;
+        let stream_clone = stream.try_clone()?;
+        let upstream_clone = upstream.try_clone()?;
+        let back = std::thread::spawn(move || forward(upstream_clone, stream_clone));
+        forward(stream, upstream);
+        back.join().ok();
+        Ok(())
+    });
+    state.lock().unwrap().peers.remove(&addr, id);
+    result
+}
+
+/// Accept connections on the public address and forward them to the websocket server,
+/// since geng's server never tells us who is on the other end
+fn run_listener<T: Model>(
+    listener: std::net::TcpListener,
+    inner: std::net::SocketAddr,
+    state: Arc<Mutex<ServerState<T>>>,
+) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let state = state.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = forward_connection(stream, inner, state) {
+                log::warn!("Failed to forward a connection: {}", e);
+            }
+        });
+    }
 }
 
 impl<T: Model> ServerState<T> {
+    fn broadcast(&mut self, message: ServerMessage<T>) -> u64 {
+        let compress = self
+            .clients
+            .values()
+            .any(|client| client.player_id.is_some() && client.compression);
+        let outgoing = Outgoing::new(message, self.config.compression_threshold, compress);
+        let mut bytes = 0;
+        for client in self.clients.values_mut() {
+            if client.player_id.is_some() {
+                bytes += client.send(&outgoing);
+            }
+        }
+        self.metrics.bytes_sent += bytes;
+        bytes
+    }
+    fn send_events(&mut self, events: Vec<T::Event>) -> u64 {
+        if events
+            .iter()
+            .all(|event| T::event_recipient(event).is_none())
+        {
+            return self.broadcast(ServerMessage::Events(events));
+        }
+        let mut bytes = 0;
+        for client in self.clients.values_mut() {
+            let player_id = match &client.player_id {
+                Some(player_id) => player_id,
+                None => continue,
+            };
+            let events: Vec<T::Event> = events
+                .iter()
+                .filter(|event| match T::event_recipient(event) {
+                    Some(recipient) => recipient == player_id,
+                    None => true,
+                })
+                .cloned()
+                .collect();
+            if events.is_empty() {
+                continue;
+            }
+            let outgoing = Outgoing::new(
+                ServerMessage::Events(events),
+                self.config.compression_threshold,
+                client.compression,
+            );
+            bytes += client.send(&outgoing);
+        }
+        self.metrics.bytes_sent += bytes;
+        bytes
+    }
     fn send_updates(&mut self) {
+        let mut bytes = 0;
         if *self.current.shared_state() != self.previous {
             let delta = self.previous.diff(self.current.shared_state());
             self.previous = self.current.shared_state().clone();
-            for client in self.clients.values_mut() {
-                client.sender.send(ServerMessage::Delta(delta.clone()));
-            }
+            bytes += self.broadcast(ServerMessage::Delta(delta));
         }
         let events = mem::replace(&mut self.events, Vec::new());
         if !events.is_empty() {
-            for client in self.clients.values_mut() {
-                client.sender.send(ServerMessage::Events(events.clone()));
-            }
+            bytes += self.send_events(events);
         }
+        self.metrics.last_update_bytes = bytes;
     }
 }
 
 struct Client<T: Model> {
-    player_id: T::PlayerId,
     client_id: usize,
     server_state: Arc<Mutex<ServerState<T>>>,
 }
 
-impl<T: Model> net::Receiver<T::Message> for Client<T> {
-    fn handle(&mut self, message: T::Message) {
+impl<T: Model> net::Receiver<ClientMessage<T>> for Client<T> {
+    fn handle(&mut self, message: ClientMessage<T>) {
         let mut state = self.server_state.lock().unwrap();
         let state: &mut ServerState<T> = &mut state;
-        state
-            .current
-            .handle_message(&mut state.events, &self.player_id, message);
+        let client = match state.clients.get_mut(&self.client_id) {
+            Some(client) => client,
+            None => return,
+        };
+        match message {
+            ClientMessage::Handshake {
+                payload,
+                compression,
+            } => {
+                if client.player_id.is_some() {
+                    return;
+                }
+                if let Some(max_connections) = state.config.max_connections {
+                    let admitted = state
+                        .clients
+                        .values()
+                        .filter(|client| client.player_id.is_some())
+                        .count();
+                    if admitted >= max_connections {
+                        log::info!("Rejected connection: server is full");
+                        let client = state.clients.get_mut(&self.client_id).unwrap();
+                        client
+                            .sender
+                            .send(ServerMessage::Rejected("Server is full".to_owned()));
+                        return;
+                    }
+                }
+                let client = state.clients.get_mut(&self.client_id).unwrap();
+                match state.current.new_player(&mut state.events, payload) {
+                    Ok((player_id, handshake)) => {
+                        client.compression =
+                            compression && state.config.compression_threshold.is_some();
+                        client.sender.send(ServerMessage::Welcome {
+                            player_id: player_id.clone(),
+                            handshake,
+                            compression: client.compression,
+                            rates: state.config.rates,
+                        });
+                        let full = Outgoing::new(
+                            ServerMessage::Full(state.current.shared_state().clone()),
+                            state.config.compression_threshold,
+                            client.compression,
+                        );
+                        state.metrics.bytes_sent += client.send(&full);
+                        client.player_id = Some(player_id);
+                    }
+                    Err(reason) => {
+                        log::info!("Rejected connection: {}", reason);
+                        client.sender.send(ServerMessage::Rejected(reason));
+                    }
+                }
+            }
+            ClientMessage::Message(message) => {
+                if let Some(player_id) = &client.player_id {
+                    state
+                        .current
+                        .handle_message(&mut state.events, player_id, message);
+                }
+            }
+        }
     }
 }
 
@@ -50,10 +357,11 @@ impl<T: Model> Drop for Client<T> {
     fn drop(&mut self) {
         let mut state = self.server_state.lock().unwrap();
         let state: &mut ServerState<T> = &mut state;
-        state
-            .current
-            .drop_player(&mut state.events, &self.player_id);
-        state.clients.remove(&self.client_id);
+        if let Some(client) = state.clients.remove(&self.client_id) {
+            if let Some(player_id) = client.player_id {
+                state.current.drop_player(&mut state.events, &player_id);
+            }
+        }
     }
 }
 
@@ -63,48 +371,90 @@ struct ServerApp<T: Model> {
 
 pub struct Server<T: Model> {
     state: Arc<Mutex<ServerState<T>>>,
+    listener: std::net::TcpListener,
+    inner_addr: std::net::SocketAddr,
     inner: net::Server<ServerApp<T>>,
 }
 
 impl<T: Model> Server<T> {
-    pub fn new<A: std::net::ToSocketAddrs + Debug + Copy>(addr: A, model: T) -> Self {
+    pub fn new<A: std::net::ToSocketAddrs + Debug + Copy>(
+        addr: A,
+        config: ServerConfig,
+        model: T,
+    ) -> Self {
+        let listener = std::net::TcpListener::bind(addr)
+            .unwrap_or_else(|e| panic!("Failed to bind {:?}: {}", addr, e));
+        // The websocket server only listens locally, everyone comes in through our listener.
+        // geng binds the address itself so the port can't be handed over, if anything takes it
+        // in the meantime the websocket server fails to start instead of running unguarded
+        let inner_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("Failed to find a free local port");
         let state = Arc::new(Mutex::new(ServerState {
+            config,
             previous: model.shared_state().clone(),
             current: model,
             events: Vec::new(),
             next_client_id: 0,
             clients: HashMap::new(),
+            peers: default(),
+            metrics: default(),
         }));
         Self {
             state: state.clone(),
-            inner: net::Server::new(ServerApp { state }, addr),
+            listener,
+            inner_addr,
+            inner: net::Server::new(ServerApp { state }, inner_addr),
         }
     }
-    pub fn handle(&self) -> net::ServerHandle {
-        self.inner.handle()
+    pub fn handle(&self) -> ServerHandle<T> {
+        ServerHandle {
+            state: self.state.clone(),
+            inner: Arc::new(Mutex::new(self.inner.handle())),
+        }
     }
     pub fn run(self) {
         let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let rates = self.state.lock().unwrap().config.rates;
+        std::thread::spawn({
+            let listener = self.listener;
+            let inner_addr = self.inner_addr;
+            let state = self.state.clone();
+            move || run_listener(listener, inner_addr, state)
+        });
         let server_thread = std::thread::spawn({
             let state = self.state;
             let running = running.clone();
             let mut timer = Timer::new();
+            let tick_time = 1.0 / rates.ticks_per_second;
+            let update_time = 1.0 / rates.updates_per_second;
             let mut unprocessed_time = 0.0;
+            let mut next_update = 0.0;
             move || {
                 while running.load(std::sync::atomic::Ordering::Relaxed) {
-                    unprocessed_time += timer.tick().as_secs_f64() as f32;
+                    let delta_time = timer.tick().as_secs_f64() as f32;
+                    unprocessed_time += delta_time;
                     unprocessed_time = unprocessed_time.min(1.0);
+                    next_update -= delta_time;
                     {
                         let mut state = state.lock().unwrap();
                         let state: &mut ServerState<T> = &mut state;
-                        while unprocessed_time > 1.0 / T::TICKS_PER_SECOND {
-                            unprocessed_time -= 1.0 / T::TICKS_PER_SECOND;
-                            state.current.tick(&mut state.events);
+                        while unprocessed_time > tick_time {
+                            unprocessed_time -= tick_time;
+                            let mut tick_timer = Timer::new();
+                            state.current.tick(&mut state.events, tick_time);
+                            let seconds = tick_timer.tick().as_secs_f64();
+                            state.metrics.ticks += 1;
+                            state.metrics.last_tick_seconds = seconds;
+                            state.metrics.total_tick_seconds += seconds;
+                        }
+                        if next_update <= 0.0 {
+                            next_update = (next_update + update_time).max(0.0);
+                            state.send_updates();
                         }
-                        state.send_updates();
                     }
                     std::thread::sleep(std::time::Duration::from_secs_f32(
-                        1.0 / T::TICKS_PER_SECOND - unprocessed_time,
+                        (tick_time - unprocessed_time).min(next_update.max(0.0)),
                     ));
                 }
             }
@@ -115,23 +465,133 @@ impl<T: Model> Server<T> {
     }
 }
 
+#[derive(Derivative)]
+#[derivative(Clone(bound = ""))]
+pub struct ServerHandle<T: Model> {
+    state: Arc<Mutex<ServerState<T>>>,
+    inner: Arc<Mutex<net::ServerHandle>>,
+}
+
+impl<T: Model> ServerHandle<T> {
+    pub fn update<R>(&self, f: impl FnOnce(&mut T, &mut Vec<T::Event>) -> R) -> R {
+        let mut state = self.state.lock().unwrap();
+        let state: &mut ServerState<T> = &mut state;
+        f(&mut state.current, &mut state.events)
+    }
+    pub fn metrics(&self) -> ServerMetrics {
+        let state = self.state.lock().unwrap();
+        ServerMetrics {
+            connections: state.clients.len(),
+            players: state
+                .clients
+                .values()
+                .filter(|client| client.player_id.is_some())
+                .count(),
+            ..state.metrics.clone()
+        }
+    }
+    pub fn kick(&self, player_id: &T::PlayerId, reason: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let state: &mut ServerState<T> = &mut state;
+        let client_id = match state
+            .clients
+            .iter()
+            .find(|(_, client)| client.player_id.as_ref() == Some(player_id))
+        {
+            Some((&client_id, _)) => client_id,
+            None => return false,
+        };
+        // Forgetting the client drops its sender and ignores anything else it sends,
+        // so it can't handshake again on the same connection
+        let mut client = state.clients.remove(&client_id).unwrap();
+        client.sender.send(ServerMessage::Kicked(reason.to_owned()));
+        drop(client);
+        state.current.drop_player(&mut state.events, player_id);
+        true
+    }
+    /// Close every connection coming from the address, returns how many there were
+    pub fn disconnect_address(&self, addr: &std::net::IpAddr) -> usize {
+        self.state.lock().unwrap().peers.disconnect(addr)
+    }
+    pub fn shutdown(&self, reason: Option<String>, restart_eta: Option<f32>) {
+        log::info!("Shutting down the server");
+        {
+            let mut state = self.state.lock().unwrap();
+            let state: &mut ServerState<T> = &mut state;
+            state.current.shutdown();
+            state.send_updates();
+            state.broadcast(ServerMessage::Shutdown(ShutdownNotice {
+                reason,
+                restart_eta,
+            }));
+        }
+        // Give the clients a moment to receive the notice before the sockets are closed
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        self.inner.lock().unwrap().shutdown();
+    }
+}
+
 impl<T: Model> net::server::App for ServerApp<T> {
     type Client = Client<T>;
     type ServerMessage = ServerMessage<T>;
-    type ClientMessage = T::Message;
+    type ClientMessage = ClientMessage<T>;
     fn connect(&mut self, mut sender: Box<dyn net::Sender<ServerMessage<T>>>) -> Client<T> {
         let mut state = self.state.lock().unwrap();
         let state: &mut ServerState<T> = &mut state;
-        let player_id = state.current.new_player(&mut state.events);
-        sender.send(ServerMessage::PlayerId(player_id.clone()));
-        sender.send(ServerMessage::Full(state.current.shared_state().clone()));
         let client_id = state.next_client_id;
-        state.clients.insert(client_id, ClientState { sender });
         state.next_client_id += 1;
+        if state.peers.pending == 0 {
+            // Not registered as a client, so everything it sends is ignored
+            log::warn!("Refused a connection that bypassed the listener");
+            sender.send(ServerMessage::Rejected("Connection refused".to_owned()));
+            return Client {
+                client_id,
+                server_state: self.state.clone(),
+            };
+        }
+        state.peers.pending -= 1;
+        state.clients.insert(
+            client_id,
+            ClientState {
+                sender,
+                player_id: None,
+                compression: false,
+            },
+        );
+        state.metrics.connections_total += 1;
         Client {
-            player_id,
             client_id,
             server_state: self.state.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_head(request: &'static [u8]) -> (Vec<u8>, Option<std::net::IpAddr>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut client = std::net::TcpStream::connect(addr).unwrap();
+            std::io::Write::write_all(&mut client, request).unwrap();
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        read_forwarded_for(&mut stream).unwrap()
+    }
+
+    #[test]
+    fn forwarded_for_is_read_from_the_request_head() {
+        let request = b"GET / HTTP/1.1\r\nHost: example.com\r\nX-Forwarded-For: 10.0.0.1, 192.0.2.7\r\nUpgrade: websocket\r\n\r\n";
+        let (head, addr) = read_head(request);
+        assert_eq!(head, request);
+        assert_eq!(addr, Some("192.0.2.7".parse().unwrap()));
+    }
+
+    #[test]
+    fn request_without_forwarded_for_keeps_the_peer() {
+        let (_, addr) = read_head(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert_eq!(addr, None);
+    }
+}